@@ -0,0 +1,186 @@
+//! 命令行参数的预处理：在交给 clap 解析之前合并配置文件、展开参数文件
+
+use std::ffi::OsString;
+use std::fs;
+use anyhow::{Context, Result};
+use clap::{Arg, Command};
+
+/// 展开 `@file` 形式的参数文件
+///
+/// 参数文件中的内容按空白（空格、制表符、换行）切分，每一段作为一个参数，
+/// 插入到 `@file` 所在的位置。第一个参数（程序名）不会被展开；
+/// `--` 之后的参数和需要取值的选项（由 `command` 的定义决定，如 `-e`、`--replace`）
+/// 后面的值原样保留，因此 `-e @Override` 搜索的是字面的 `@Override`。
+pub fn expand_arg_files<I>(args: I, command: &Command) -> Result<Vec<OsString>>
+where
+    I: IntoIterator<Item = OsString>,
+{
+    let value_options = ValueOptions::new(command);
+    let mut expanded = Vec::new();
+    let mut args = args.into_iter();
+    expanded.extend(args.next());
+    while let Some(arg) = args.next() {
+        let Some(s) = arg.to_str() else {
+            expanded.push(arg);
+            continue;
+        };
+        if s == "--" {
+            expanded.push(arg);
+            expanded.extend(args.by_ref());
+            break;
+        }
+        if value_options.expects_value(s) {
+            expanded.push(arg);
+            expanded.extend(args.next());
+            continue;
+        }
+        let arg_file = match s.strip_prefix('@') {
+            Some(file) if !file.is_empty() => file,
+            _ => {
+                expanded.push(arg);
+                continue;
+            }
+        };
+        let content = fs::read_to_string(arg_file)
+            .with_context(|| {
+                format!(
+                    "Failed to read argument file: {} (use -e or -- for a pattern starting with '@')",
+                    arg_file
+                )
+            })?;
+        expanded.extend(content.split_whitespace().map(OsString::from));
+    }
+    Ok(expanded)
+}
+
+// 需要取值的选项名
+struct ValueOptions {
+    shorts: Vec<char>,
+    longs: Vec<String>,
+}
+
+impl ValueOptions {
+    fn new(command: &Command) -> Self {
+        let options: Vec<&Arg> = command
+            .get_arguments()
+            .filter(|arg| !arg.is_positional() && arg.get_action().takes_values())
+            .collect();
+        ValueOptions {
+            shorts: options.iter().filter_map(|arg| arg.get_short()).collect(),
+            longs: options
+                .iter()
+                .filter_map(|arg| arg.get_long())
+                .map(String::from)
+                .collect(),
+        }
+    }
+
+    // `arg` 是否是一个值在下一个参数中的选项：`--regexp`（不含 `=`），
+    // 或以需要取值的短选项结尾的短选项组，如 `-e`、`-Fe`
+    fn expects_value(&self, arg: &str) -> bool {
+        if let Some(long) = arg.strip_prefix("--") {
+            return !long.contains('=') && self.longs.iter().any(|name| name == long);
+        }
+        let Some(shorts) = arg.strip_prefix('-') else {
+            return false;
+        };
+        // 短选项组中第一个需要取值的选项会把剩下的字符当作它的值
+        for (index, c) in shorts.char_indices() {
+            if self.shorts.contains(&c) {
+                return index + c.len_utf8() == shorts.len();
+            }
+        }
+        false
+    }
+}
+
+/// 指向默认参数配置文件的环境变量
+pub const CONFIG_ENV: &str = "GREPDOJO_CONFIG";
+
+/// 把 `GREPDOJO_CONFIG` 指向的配置文件中的参数插入到程序名之后
+///
+/// 配置文件每行一个参数（原样使用，不再按空白切分，例如 `--max-count=5`），
+/// 空行和以 `#` 开头的行会被忽略。配置中的参数排在命令行参数之前，
+/// 同一个参数在命令行中再次出现时以命令行为准。
+pub fn prepend_config_args(args: Vec<OsString>) -> Result<Vec<OsString>> {
+    let Some(config_path) = std::env::var_os(CONFIG_ENV).filter(|p| !p.is_empty()) else {
+        return Ok(args);
+    };
+    let content = fs::read_to_string(&config_path).with_context(|| {
+        format!("Failed to read config file: {}", config_path.to_string_lossy())
+    })?;
+
+    let config_args = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(OsString::from);
+
+    let mut args = args.into_iter();
+    let mut merged: Vec<OsString> = args.next().into_iter().collect();
+    merged.extend(config_args);
+    merged.extend(args);
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+    use crate::Args;
+
+    fn expand(args: &[&str]) -> Result<Vec<String>> {
+        let args = std::iter::once("grepdojo").chain(args.iter().copied()).map(OsString::from);
+        let expanded = expand_arg_files(args, &Args::command())?;
+        Ok(expanded.into_iter().map(|arg| arg.into_string().unwrap()).collect())
+    }
+
+    #[test]
+    fn expands_arg_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("args");
+        fs::write(&file, "-i\n--max-count 3\tfoo\n").unwrap();
+        let arg = format!("@{}", file.display());
+        assert_eq!(
+            expand(&[&arg, "src"]).unwrap(),
+            ["grepdojo", "-i", "--max-count", "3", "foo", "src"]
+        );
+    }
+
+    #[test]
+    fn missing_arg_file_is_an_error() {
+        let err = expand(&["@/nonexistent/grepdojo-args"]).unwrap_err();
+        assert!(err.to_string().contains("Failed to read argument file"));
+    }
+
+    #[test]
+    fn option_values_starting_with_at_are_kept() {
+        for args in [
+            &["-e", "@Override", "src"][..],
+            &["--regexp", "@Override", "src"],
+            &["-Fe", "@Override", "src"],
+            &["-r", "@x", "foo"],
+            &["--replace", "@x", "foo"],
+        ] {
+            assert_eq!(expand(args).unwrap()[1..], *args);
+        }
+    }
+
+    #[test]
+    fn arguments_after_double_dash_are_kept() {
+        let args = ["-F", "--", "@Override", "src"];
+        assert_eq!(expand(&args).unwrap()[1..], args);
+    }
+
+    #[test]
+    fn attached_values_do_not_consume_the_next_argument() {
+        // `-efoo` 和 `--regexp=foo` 的值已经附在选项上，后面的 `@file` 仍然展开
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("args");
+        fs::write(&file, "src").unwrap();
+        let arg = format!("@{}", file.display());
+        for option in ["-efoo", "--regexp=foo"] {
+            assert_eq!(expand(&[option, &arg]).unwrap(), ["grepdojo", option, "src"]);
+        }
+    }
+}
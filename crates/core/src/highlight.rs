@@ -0,0 +1,23 @@
+//! 颜色输出：为匹配行中的每个匹配填充捕获组的位置，打印器据此分层高亮
+
+use matcher::Matcher;
+use searcher::{Line, LineKind};
+
+/// 把每个匹配的捕获组（组 1 及以后、非空）记录到 `Match::groups`
+///
+/// 捕获组按整个匹配的位置与 `line.matches` 对应；找不到对应项的匹配保持原样。
+pub fn attach_groups<M: Matcher>(matcher: &M, line: &mut Line) {
+    if line.kind != LineKind::Match || line.matches.is_empty() {
+        return;
+    }
+    let captures = matcher.captures(&line.content);
+    for m in &mut line.matches {
+        let Some(caps) = captures.iter().find(|caps| caps.span() == (m.start, m.end)) else {
+            continue;
+        };
+        m.groups = (1..caps.len())
+            .filter_map(|index| caps.get(index))
+            .filter(|(start, end)| start < end)
+            .collect();
+    }
+}
@@ -1,37 +1,513 @@
+mod config;
+mod highlight;
+mod pre;
+mod replace;
+mod stats;
+mod types;
+mod walk;
+
 pub use run_app as run; 
-use std::sync::{Arc, Mutex};
-use walkdir::WalkDir;
+use std::io::{self, IsTerminal, Read, Write};
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::path::{Path, PathBuf};
-use clap::Parser;
-use matcher::RegexMatcher;
-use searcher::Searcher;
-use printer::Printer;
+use clap::{CommandFactory, Parser};
+use matcher::{BytesRegexMatcher, FancyRegexMatcher, LiteralMatcher, MatchScope, Matcher, MultiLiteralMatcher, OrMatcher, RegexMatcher, is_pure_literal};
+use searcher::{Encoding, LineKind, SearchStats, Searcher, SearcherConfig};
+use stats::RunStats;
+use printer::{Printer, PrinterConfig};
 use anyhow::{Context, Result, bail};
-use ignore::Ignore;
+use ignore::{GlobMatcher, Ignore, IgnoreConfig};
 use rayon::prelude::*;
+use types::TypeDefs;
+use walk::{GlobRule, SortBy, WalkFilter, sort_files};
 
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+// 配置文件中的参数排在命令行参数之前，重复出现时以最后一次为准
+#[command(author, version, about, long_about = None, args_override_self = true)]
 pub struct Args {
-    #[arg(help = "The regex pattern to search for")]
-    pattern: String,
+    #[arg(help = "The regex pattern to search for (omit when using -e or -f)", required_unless_present_any = ["regexp", "file", "type_list"])]
+    pattern: Option<String>,
 
-    #[arg(help = "Files or directories to search", default_value = ".")]
+    #[arg(help = "Files or directories to search ('-' for stdin; default: stdin when piped, else current directory)")]
     paths: Vec<PathBuf>,
 
+    /// A pattern to search for (repeatable); when given, every positional argument is a path
+    #[arg(long, short = 'e', value_name = "PATTERN")]
+    regexp: Vec<String>,
+
+    /// Read patterns from FILE, one per line ('-' for stdin; repeatable); an empty line
+    /// matches every line. Combines with -e, and every positional argument is a path
+    #[arg(long, short = 'f', value_name = "FILE")]
+    file: Vec<PathBuf>,
+
+    /// Treat all patterns as literal strings instead of regular expressions
+    #[arg(long, short = 'F')]
+    fixed_strings: bool,
+
+    /// Only report matches that form whole words (not preceded or followed by a word character)
+    #[arg(long, short = 'w')]
+    word_regexp: bool,
+
+    /// Only report matches that span the whole line (takes precedence over -w)
+    #[arg(long, short = 'x')]
+    line_regexp: bool,
+
+    /// Let matches span multiple lines (e.g. 'foo\nbar'); '^' and '$' match at every line,
+    /// and every line a match touches is printed
+    #[arg(long, short = 'U', conflicts_with = "hex")]
+    multiline: bool,
+
+    /// Use the backtracking regex engine, which supports look-around ('(?<=\$)\d+') and
+    /// backreferences ('(\w+) \1'); slower on patterns that need backtracking
+    #[arg(long, short = 'P', conflicts_with = "hex")]
+    pcre2: bool,
+
+    /// With -P, match \w, \d, \s and \b (and -w word boundaries) against ASCII only
+    #[arg(long, requires = "pcre2")]
+    no_pcre2_unicode: bool,
+
+    /// Search case-insensitively if a pattern has no uppercase letters (decided per pattern)
+    #[arg(long, short = 'S')]
+    smart_case: bool,
+
     /// Number of threads to use for parallel search (0 = auto-detect, 1 = single-threaded)
     #[arg(long, short = 'j', default_value = "0", help = "Number of threads (0 = auto, 1 = single-threaded)")]
     jobs: usize,
+
+    /// Show NUM lines before and after each match
+    #[arg(long, short = 'C', value_name = "NUM", default_value = "0")]
+    context: usize,
+
+    /// Show NUM lines after each match (overrides -C for trailing context)
+    #[arg(long, short = 'A', value_name = "NUM")]
+    after_context: Option<usize>,
+
+    /// Show NUM lines before each match (overrides -C for leading context)
+    #[arg(long, short = 'B', value_name = "NUM")]
+    before_context: Option<usize>,
+
+    /// Stop searching a file after NUM matching lines (non-matching lines with -v);
+    /// the limit applies to each file separately, also when searching directories
+    #[arg(long, short = 'm', value_name = "NUM")]
+    max_count: Option<usize>,
+
+    /// Only read and search the first NUM bytes of each file (line numbers cover only that part)
+    #[arg(long, value_name = "NUM")]
+    max_read_bytes: Option<usize>,
+
+    /// Search the standard output of COMMAND run with each file path as its only argument
+    #[arg(long, value_name = "COMMAND")]
+    pre: Option<String>,
+
+    /// Cache --pre output in DIR, keyed by path and modification time, and reuse it while
+    /// the file is unchanged (the cache is bounded; oldest entries are removed first)
+    #[arg(long, value_name = "DIR", requires = "pre")]
+    pre_cache_dir: Option<PathBuf>,
+
+    /// File encoding: auto (per-file detection from BOM and content), utf-8, utf-16le or utf-16be
+    #[arg(long, short = 'E', value_name = "ENCODING", default_value = "auto", value_parser = Encoding::from_label)]
+    encoding: Encoding,
+
+    /// Treat input as NUL-separated records instead of lines; output records are NUL-terminated
+    #[arg(long)]
+    null_data: bool,
+
+    /// Select lines that do not match the pattern; with -C the context shown
+    /// around them is the matching lines
+    #[arg(long, short = 'v')]
+    invert_match: bool,
+
+    /// Suppress lines whose content was already printed, in any file or at any line number
+    /// (only the content is compared, not the path or line number prefix)
+    #[arg(long)]
+    dedup_lines: bool,
+
+    /// Only descend into directories whose name matches GLOB (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    include_dir: Vec<String>,
+
+    /// Include or exclude (with a leading '!') files matching GLOB; overrides --type, and an
+    /// including GLOB also overrides .gitignore.
+    /// GLOB matches names at any depth unless it contains '/' (a leading '/' anchors it to the search root)
+    #[arg(long, short = 'g', value_name = "GLOB")]
+    glob: Vec<String>,
+
+    /// Only search files of TYPE (repeatable)
+    #[arg(long = "type", short = 't', value_name = "TYPE")]
+    file_type: Vec<String>,
+
+    /// Don't search files of TYPE; takes precedence over --type (repeatable)
+    #[arg(long, short = 'T', value_name = "TYPE")]
+    type_not: Vec<String>,
+
+    /// Print every known file type and its globs, then exit
+    #[arg(long)]
+    type_list: bool,
+
+    /// Add a file type definition as NAME:GLOB[,GLOB...] (repeatable)
+    #[arg(long, value_name = "TYPESPEC")]
+    type_add: Vec<String>,
+
+    /// Remove the globs of a built-in type, applied before --type-add (repeatable)
+    #[arg(long, value_name = "TYPE")]
+    type_clear: Vec<String>,
+
+    /// Group matches under a file path heading (default when stdout is a terminal)
+    #[arg(long, overrides_with = "no_heading")]
+    heading: bool,
+
+    /// Print the file path on every line (default when output is piped)
+    #[arg(long, overrides_with = "heading")]
+    no_heading: bool,
+
+    /// Print the file path for every match (default)
+    #[arg(long, short = 'H', overrides_with = "no_filename")]
+    with_filename: bool,
+
+    /// Never print the file path, only line numbers and contents (or counts)
+    #[arg(long, short = 'I', overrides_with = "with_filename")]
+    no_filename: bool,
+
+    /// Print only the matched parts of a line, one match per output line
+    /// (with --heading, further matches of a line are indented instead of repeating its number)
+    #[arg(long, short = 'o')]
+    only_matching: bool,
+
+    /// Print the 0-based byte offset of each line (of each match with -o) after the line number
+    #[arg(long, short = 'b')]
+    byte_offset: bool,
+
+    /// Pad line numbers to a common width; 'auto' uses the widest line number printed for each file
+    #[arg(long, value_enum, value_name = "WIDTH")]
+    line_number_width: Option<LineNumberWidth>,
+
+    /// Truncate lines longer than NUM characters, marking the cut with '...'
+    #[arg(long, short = 'M', value_name = "NUM")]
+    max_columns: Option<usize>,
+
+    /// When truncating with --max-columns, center the kept part on the first match
+    #[arg(long, requires = "max_columns")]
+    max_columns_preview: bool,
+
+    /// Follow printed paths with a NUL byte instead of ':'; with --heading every record
+    /// (heading, line, '--') is NUL-terminated and each file group ends with an empty record
+    #[arg(long, short = '0')]
+    null: bool,
+
+    /// Shell-quote printed paths that contain ':', whitespace, newlines or other special characters
+    #[arg(long)]
+    quote: bool,
+
+    /// When to use colors: paths magenta, line numbers green, matches bold red, capture groups also underlined.
+    /// auto colors only when stdout is a terminal and NO_COLOR is unset
+    #[arg(long, value_enum, value_name = "WHEN", default_value = "auto")]
+    color: ColorChoice,
+
+    /// Print every match on its own line as path:line:column:content (for Vim's quickfix list)
+    #[arg(long, conflicts_with_all = ["count", "count_matches", "json", "hex"])]
+    vimgrep: bool,
+
+    /// Print results as JSON Lines events: begin/end per file, match/context with line numbers,
+    /// byte offsets and submatch ranges, and a final summary
+    #[arg(long, conflicts_with_all = ["count", "count_matches"])]
+    json: bool,
+
+    /// Search raw bytes (binary files included) and print each match as a hex dump
+    /// (`offset: hex bytes  ascii`); use (?-u)\xNN in the pattern to match arbitrary bytes
+    #[arg(long, conflicts_with_all = ["count", "count_matches", "json", "invert_match"])]
+    hex: bool,
+
+    /// Separator between fields on matching lines [default: :]
+    #[arg(long, value_name = "SEP")]
+    field_match_separator: Option<String>,
+
+    /// Separator between fields on context lines [default: -]
+    #[arg(long, value_name = "SEP")]
+    field_context_separator: Option<String>,
+
+    /// Print nothing; stop searching as soon as any match is found
+    #[arg(long, short = 'q')]
+    quiet: bool,
+
+    /// Print only the paths of files with at least one match; each file stops being read at its first match
+    #[arg(long, short = 'l', conflicts_with_all = ["count", "count_matches", "json", "hex"])]
+    files_with_matches: bool,
+
+    /// Print only the paths of searched files that contain no match
+    #[arg(long, conflicts_with_all = ["files_with_matches", "count", "count_matches", "json", "hex"])]
+    files_without_match: bool,
+
+    /// Print the number of matching lines per file
+    #[arg(long, short = 'c', conflicts_with = "count_matches")]
+    count: bool,
+
+    /// Print the total number of matches (not lines) per file
+    #[arg(long)]
+    count_matches: bool,
+
+    /// Print statistics after the results: matched lines, matches, files searched and
+    /// skipped by ignore rules, bytes searched and elapsed time
+    #[arg(long)]
+    stats: bool,
+
+    /// With --count or --count-matches, print only the sum over all searched files
+    #[arg(long)]
+    total: bool,
+
+    /// Flush output after every line (default when stdout is a terminal)
+    #[arg(long, overrides_with = "no_line_buffered")]
+    line_buffered: bool,
+
+    /// Buffer output in blocks for throughput (default when output is piped)
+    #[arg(long, overrides_with = "line_buffered")]
+    no_line_buffered: bool,
+
+    /// Limit directory traversal to NUM levels below each search path
+    #[arg(long, short = 'd', value_name = "NUM")]
+    max_depth: Option<usize>,
+
+    /// Follow symbolic links while walking directories (loops are detected and skipped)
+    #[arg(long, short = 'L')]
+    follow: bool,
+
+    /// Don't respect ignore files (.gitignore, .ignore); --ignore-file still applies
+    #[arg(long)]
+    no_ignore: bool,
+
+    /// Don't respect .gitignore files
+    #[arg(long)]
+    no_ignore_vcs: bool,
+
+    /// Don't respect .ignore files
+    #[arg(long)]
+    no_ignore_dot: bool,
+
+    /// Also apply the gitignore-style rules in FILE, relative to each search root;
+    /// .gitignore files take precedence (may be repeated)
+    #[arg(long, value_name = "FILE")]
+    ignore_file: Vec<PathBuf>,
+
+    /// Search hidden files and directories (names starting with '.')
+    #[arg(long)]
+    hidden: bool,
+
+    /// Search binary files as if they were text
+    #[arg(long, short = 'a')]
+    text: bool,
+
+    /// Reduce filtering: -u = --no-ignore, -uu = also --hidden, -uuu = also --text
+    #[arg(long, short = 'u', action = clap::ArgAction::Count)]
+    unrestricted: u8,
+
+    /// Sort files by the given key (searches single-threaded)
+    #[arg(long, value_enum, value_name = "SORTBY", default_value = "none")]
+    sort: SortBy,
+
+    /// Replace every match with TEXT in the output ($1 / ${name} refer to capture groups);
+    /// with --count, report how many matching lines and files the replacement applies to
+    #[arg(long, short = 'r', value_name = "TEXT")]
+    replace: Option<String>,
+}
+
+/// `--color` 的取值
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum ColorChoice {
+    Never,
+    /// 标准输出是终端且没有设置 `NO_COLOR` 时着色
+    Auto,
+    Always,
 }
 
-pub fn run_app() -> Result<()> {
-    let args = Args::parse();
+/// `--line-number-width` 的取值
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum LineNumberWidth {
+    /// 按每个文件中输出的最大行号对齐
+    Auto,
+}
+
+/// 每个文件的搜索结果如何输出
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputMode {
+    /// 逐行输出匹配（及上下文）
+    Lines,
+    /// 输出每个文件的匹配行数
+    Count,
+    /// 输出每个文件的匹配总数（一行多个匹配分别计数）
+    CountMatches,
+    /// 按字节搜索，匹配输出为十六进制转储（`--hex`）
+    Hex,
+    /// 不输出，只记录是否找到匹配（`--quiet`）
+    Quiet,
+    /// 只输出包含匹配的文件路径（`-l`）
+    FilesWithMatches,
+    /// 只输出不包含匹配的文件路径（`--files-without-match`）
+    FilesWithoutMatch,
+}
 
-    let matcher = RegexMatcher::new(&args.pattern)
-        .context(format!("Invalid regex pattern: '{}'", args.pattern))?;
+/// 一次运行中所有文件共享的搜索状态
+struct SearchWorker {
+    searcher: Searcher<Box<dyn Matcher + Send + Sync>>,
+    printer: Mutex<Printer>,
+    mode: OutputMode,
+    /// `--replace` 的替换模板
+    replace: Option<String>,
+    /// `--replace --count`：替换作用到的行数（即匹配行数）与文件数（所有文件汇总）
+    changed_lines: AtomicUsize,
+    changed_files: AtomicUsize,
+    /// `--total`：不输出每个文件的计数，只在最后输出所有文件的总和
+    total: bool,
+    total_count: AtomicUsize,
+    /// 颜色输出时需要每个匹配的捕获组位置
+    capture_groups: bool,
+    /// 是否已经找到匹配（决定退出码）；`--quiet` 时所有任务在开始搜索下一个文件前检查，
+    /// 一旦置位就不再搜索
+    matched: AtomicBool,
+    /// `--pre` 预处理命令
+    pre: Option<pre::Preprocessor>,
+    /// 遍历目录时无法访问的条目数；遍历不会因此中断，但最后以错误结束
+    walk_errors: AtomicUsize,
+    /// 运行统计；`--stats` 时在最后输出
+    stats: RunStats,
+    print_stats: bool,
+}
 
-    let searcher = Arc::new(Searcher::new(matcher));
-    let printer = Arc::new(Mutex::new(Printer::new()));
+impl SearchWorker {
+    // `--quiet` 已经找到匹配，剩下的文件都不需要再搜索
+    fn stopped(&self) -> bool {
+        self.mode == OutputMode::Quiet && self.matched.load(Ordering::Relaxed)
+    }
+}
+
+/// 运行一次搜索，返回是否找到了匹配
+///
+/// 退出码遵循 grep 的约定（由 `main` 转换）：找到匹配为 0，没有匹配为 1，出错为 2。
+/// `--quiet` 时只要找到匹配就以 0 退出，即使其他路径出错。
+/// `--files-without-match` 时“找到”指输出了至少一个没有匹配的文件。
+pub fn run_app() -> Result<bool> {
+    let raw_args = config::expand_arg_files(std::env::args_os(), &Args::command())?;
+    let args = Args::parse_from(config::prepend_config_args(raw_args)?);
+
+    validate_args(&args)?;
+
+    if args.type_list {
+        return match print_type_list(&args) {
+            Err(e) if is_broken_pipe(&e) => Ok(true),
+            result => result.map(|()| true),
+        };
+    }
+
+    let (patterns, paths) = patterns_and_paths(&args)?;
+    let (patterns, fixed_strings) = match args.smart_case {
+        true => apply_smart_case(patterns, args.fixed_strings),
+        false => (patterns, args.fixed_strings),
+    };
+    let scope = if args.line_regexp {
+        MatchScope::Line
+    } else if args.word_regexp {
+        MatchScope::Word
+    } else {
+        MatchScope::Any
+    };
+    let engine = match (args.hex, args.pcre2) {
+        (true, _) => Engine::Bytes,
+        (false, true) => Engine::Backtracking { unicode: !args.no_pcre2_unicode },
+        (false, false) => Engine::Default,
+    };
+    let matcher = build_matcher(&patterns, fixed_strings, engine, args.multiline, scope)?;
+
+    let mode = if args.quiet {
+        OutputMode::Quiet
+    } else if args.files_with_matches {
+        OutputMode::FilesWithMatches
+    } else if args.files_without_match {
+        OutputMode::FilesWithoutMatch
+    } else if args.hex {
+        OutputMode::Hex
+    } else if args.count {
+        OutputMode::Count
+    } else if args.count_matches {
+        OutputMode::CountMatches
+    } else {
+        OutputMode::Lines
+    };
+    // 计数模式和 --vimgrep 不输出上下文行；-A / -B 分别覆盖 -C 的对应一侧
+    let (before_context, after_context) = match mode {
+        OutputMode::Lines if !args.vimgrep => (
+            args.before_context.unwrap_or(args.context),
+            args.after_context.unwrap_or(args.context),
+        ),
+        _ => (0, 0),
+    };
+
+    // max-count 只统计命中的行（-v 时为不匹配的行），由搜索器统一执行；
+    // 达到上限后仍会输出最后一个命中行的后置上下文
+    // --quiet、-l 和 --files-without-match 只需要知道有没有匹配，每个文件在第一个命中行处停止
+    let files_only = matches!(
+        mode,
+        OutputMode::Quiet | OutputMode::FilesWithMatches | OutputMode::FilesWithoutMatch
+    );
+    let max_count = match files_only {
+        true => Some(1),
+        false => args.max_count,
+    };
+    let searcher_config = SearcherConfig {
+        before_context,
+        after_context,
+        max_count,
+        invert_match: args.invert_match,
+        encoding: args.encoding,
+        null_data: args.null_data,
+        // 打印器自己输出行结束符
+        include_line_terminator: false,
+        binary_detection: !(args.text || args.unrestricted >= 3),
+        max_read_bytes: args.max_read_bytes,
+        multiline: args.multiline,
+        mmap_threshold: None,
+        // --stats 需要统计匹配数，仍然查找匹配位置
+        lines_only: files_only && !args.stats,
+    };
+    let color = use_color(&args);
+    let printer_config = PrinterConfig {
+        context: before_context > 0 || after_context > 0,
+        dedup_lines: args.dedup_lines,
+        heading: use_heading(&args),
+        only_matching: args.only_matching,
+        field_match_separator: args.field_match_separator.clone().unwrap_or_else(|| ":".to_string()),
+        field_context_separator: args.field_context_separator.clone().unwrap_or_else(|| "-".to_string()),
+        line_buffered: use_line_buffering(&args),
+        with_filename: !args.no_filename,
+        max_columns: args.max_columns,
+        max_columns_preview: args.max_columns_preview,
+        null_data: args.null_data,
+        // --quiet 时连 summary 也不输出
+        json: args.json && mode == OutputMode::Lines,
+        null: args.null,
+        quote_paths: args.quote,
+        color,
+        byte_offset: args.byte_offset,
+        align_line_numbers: args.line_number_width == Some(LineNumberWidth::Auto),
+        vimgrep: args.vimgrep,
+    };
+
+    let worker = SearchWorker {
+        searcher: Searcher::with_config(matcher, searcher_config),
+        printer: Mutex::new(Printer::with_config(printer_config)),
+        mode,
+        replace: args.replace.clone(),
+        changed_lines: AtomicUsize::new(0),
+        changed_files: AtomicUsize::new(0),
+        total: args.total,
+        total_count: AtomicUsize::new(0),
+        capture_groups: color,
+        matched: AtomicBool::new(false),
+        pre: args.pre.clone().map(|command| pre::Preprocessor::new(command, args.pre_cache_dir.clone())),
+        walk_errors: AtomicUsize::new(0),
+        stats: RunStats::new(),
+        // JSON 的 summary 事件已经包含统计，--quiet 不输出任何内容
+        print_stats: args.stats && !args.json && mode != OutputMode::Quiet,
+    };
     
     // 如果指定了 jobs > 1，设置 rayon 的线程池
     if args.jobs > 1 {
@@ -43,63 +519,448 @@ pub fn run_app() -> Result<()> {
 
     // jobs == 1 表示单线程，jobs == 0 或 jobs > 1 表示并行
     let use_parallel = args.jobs != 1;
-    process_paths(searcher.clone(), printer.clone(), &args.paths, use_parallel)
+    let filter = WalkFilter {
+        include_dirs: args.include_dir.iter().map(|glob| GlobMatcher::new(glob)).collect(),
+        globs: args.glob.iter().map(|glob| GlobRule::new(glob)).collect(),
+        types: type_defs(&args)?.select(&args.file_type, &args.type_not)?,
+        max_depth: args.max_depth,
+        follow: args.follow,
+        sort: args.sort,
+        ignore: ignore_config(&args),
+        ignore_files: args.ignore_file.clone(),
+        hidden: args.hidden || args.unrestricted >= 2,
+        explicit_files: explicit_files_in_dirs(&paths),
+    };
+    let result = process_paths(&worker, &paths, use_parallel, &filter);
+    // 即使搜索中途出错，也要把已经产生的输出写完
+    let finished = finish_output(&worker);
+    let matched = worker.matched.load(Ordering::Relaxed);
+    match result.and(finished) {
+        // 下游提前关闭了管道（例如 `| head`）：与其他 Unix 工具一样静默地正常退出
+        Err(e) if is_broken_pipe(&e) => Ok(true),
+        // 错误已经在搜索过程中报告到标准错误，结果只取决于是否找到了匹配
+        Err(_) if worker.mode == OutputMode::Quiet && matched => Ok(true),
+        Err(e) => Err(e),
+        Ok(()) => Ok(matched),
+    }
+}
+
+// 输出汇总信息并刷新缓冲区
+fn finish_output(worker: &SearchWorker) -> Result<()> {
+    let mut printer = worker.printer.lock().unwrap();
+    if worker.total {
+        printer.print_summary(&worker.total_count.load(Ordering::Relaxed).to_string())?;
+    }
+    if worker.print_stats {
+        for line in worker.stats.summary() {
+            printer.print_summary(&line)?;
+        }
+    }
+    // 替换预览：在每个文件的行数之后汇总总数
+    if worker.replace.is_some() && worker.mode == OutputMode::Count {
+        printer.print_summary(&format!(
+            "{} lines in {} files would be changed",
+            worker.changed_lines.load(Ordering::Relaxed),
+            worker.changed_files.load(Ordering::Relaxed),
+        ))?;
+    }
+    printer.finish()?;
+    Ok(())
+}
+
+/// 错误是否由写入已关闭的管道引起
+fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+    })
+}
+
+/// 代表标准输入的路径参数
+const STDIN_PATH: &str = "-";
+/// 输出中标准输入的名字
+const STDIN_NAME: &str = "<stdin>";
+
+// `--type-list`：输出所有类型（包括 --type-add 定义的）后退出
+fn print_type_list(args: &Args) -> Result<()> {
+    let mut out = io::stdout().lock();
+    for line in type_defs(args)?.list() {
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+// 内置类型表加上命令行的修改：先执行所有 --type-clear，再执行 --type-add，
+// 这样 `--type-clear rust --type-add 'rust:*.rs.in'` 可以完整地重新定义类型
+fn type_defs(args: &Args) -> Result<TypeDefs> {
+    let mut defs = TypeDefs::default();
+    for name in &args.type_clear {
+        defs.clear(name);
+    }
+    for spec in &args.type_add {
+        defs.add(spec)?;
+    }
+    Ok(defs)
+}
+
+// 使用 -e 时所有位置参数都是路径；没有给出路径时，标准输入是管道或文件就搜索标准输入，
+// 否则搜索当前目录
+fn patterns_and_paths(args: &Args) -> Result<(Vec<String>, Vec<PathBuf>)> {
+    let mut paths = Vec::new();
+    let patterns = if args.regexp.is_empty() && args.file.is_empty() {
+        args.pattern.iter().cloned().collect()
+    } else {
+        paths.extend(args.pattern.iter().map(PathBuf::from));
+        let mut patterns = args.regexp.clone();
+        for file in &args.file {
+            patterns.extend(read_pattern_file(file)?);
+        }
+        patterns
+    };
+    paths.extend(args.paths.iter().cloned());
+    if paths.is_empty() {
+        if std::io::stdin().is_terminal() {
+            paths.push(PathBuf::from("."));
+        } else {
+            paths.push(PathBuf::from(STDIN_PATH));
+        }
+    }
+    Ok((patterns, paths))
+}
+
+/// 读取 `-f` 的模式文件：每行一个模式，去掉行尾的 `\r`。
+/// 空文件没有任何模式（什么都不匹配），空行是匹配所有行的空模式
+fn read_pattern_file(path: &Path) -> Result<Vec<String>> {
+    let content = if path.as_os_str() == STDIN_PATH {
+        let mut content = String::new();
+        std::io::stdin().read_to_string(&mut content)?;
+        content
+    } else {
+        std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read pattern file: {}", path.display()))?
+    };
+    Ok(content
+        .lines()
+        .map(|line| line.strip_suffix('\r').unwrap_or(line).to_string())
+        .collect())
+}
+
+/// `--smart-case`：不含大写字母的模式不区分大小写，每个模式单独判断
+///
+/// 在构造匹配器之前把这样的模式改写为以 `(?i)` 开头的正则。匹配器的字面量、稀有字节
+/// 和行首分支预过滤都区分大小写，模式以内联标志开头时它们不会启用，因此不会漏掉
+/// 大小写不同的行。`-F` 的模式先转义，改写之后所有模式都按正则处理。
+fn apply_smart_case(patterns: Vec<String>, fixed_strings: bool) -> (Vec<String>, bool) {
+    if patterns.iter().all(|pattern| has_uppercase(pattern, fixed_strings)) {
+        return (patterns, fixed_strings);
+    }
+    let patterns = patterns
+        .into_iter()
+        .map(|pattern| {
+            let insensitive = !has_uppercase(&pattern, fixed_strings);
+            let pattern = if fixed_strings { regex::escape(&pattern) } else { pattern };
+            if insensitive { format!("(?i){}", pattern) } else { pattern }
+        })
+        .collect();
+    (patterns, false)
+}
+
+// 模式中是否有大写字母。正则中的转义序列（`\W`、`\S`、`\p{Lu}` 等）不是字面量，不计入
+fn has_uppercase(pattern: &str, fixed_strings: bool) -> bool {
+    if fixed_strings {
+        return pattern.chars().any(char::is_uppercase);
+    }
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                // `\p{..}` / `\P{..}` 的类名整体跳过
+                Some('p' | 'P') if chars.as_str().starts_with('{') => {
+                    chars.by_ref().find(|&c| c == '}');
+                }
+                _ => {}
+            },
+            c if c.is_uppercase() => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// 检查互相冲突的输出参数组合，在开始搜索之前给出明确的错误
+///
+/// `--json` 和 `--hex` 有各自固定的输出格式，调整文本输出格式的参数对它们没有意义
+fn validate_args(args: &Args) -> Result<()> {
+    let format_flags = [
+        ("--field-match-separator", args.field_match_separator.is_some()),
+        ("--field-context-separator", args.field_context_separator.is_some()),
+        ("--heading", args.heading),
+        ("--only-matching", args.only_matching),
+        ("--byte-offset", args.byte_offset),
+        ("--line-number-width", args.line_number_width.is_some()),
+        ("--max-columns", args.max_columns.is_some()),
+        ("--replace", args.replace.is_some()),
+        ("--quote", args.quote),
+        ("--null", args.null),
+    ];
+    if args.total && !(args.count || args.count_matches) {
+        bail!("--total requires --count or --count-matches");
+    }
+    let formats = [("--json", args.json), ("--hex", args.hex)];
+    for (format, format_set) in formats {
+        if !format_set {
+            continue;
+        }
+        if let Some((flag, _)) = format_flags.iter().find(|(_, set)| *set) {
+            bail!("{} cannot be used with {}", flag, format);
+        }
+    }
+    Ok(())
+}
+
+/// 编译正则使用的引擎
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Engine {
+    /// regex crate，带字面量预过滤
+    Default,
+    /// 按字节匹配（`--hex`）
+    Bytes,
+    /// 支持环视和反向引用的回溯引擎（`-P`）；`unicode` 为 false 时字符类只认 ASCII
+    Backtracking { unicode: bool },
+}
+
+/// 根据模式选择匹配器
+///
+/// 单个 `-F` 模式直接用 memmem 查找，不编译正则；多个模式都是固定字符串
+/// （`-F`，或模式本身不含元字符）时使用 Aho-Corasick，否则每个模式用 `engine`
+/// 各编译一个正则，任意一个命中即可。
+///
+/// 限定了匹配范围（`-w` / `-x`）时字面量匹配器无法检查边界，固定字符串转义后按正则处理。
+/// 多行模式（`-U`）中 `^` / `$` 匹配每一行的行首和行尾，而不只是数据的开头和结尾。
+fn build_matcher(
+    patterns: &[String],
+    fixed_strings: bool,
+    engine: Engine,
+    multiline: bool,
+    scope: MatchScope,
+) -> Result<Box<dyn Matcher + Send + Sync>> {
+    let literal_only = scope == MatchScope::Any;
+    let compile = |pattern: &String| -> Result<Box<dyn Matcher + Send + Sync>> {
+        if fixed_strings && literal_only {
+            // 固定字符串不经过正则，按字节查找同样适用于 --hex
+            return Ok(Box::new(LiteralMatcher::new(pattern)));
+        }
+        let pattern = if fixed_strings { regex::escape(pattern) } else { pattern.clone() };
+        let matcher: Box<dyn Matcher + Send + Sync> = match engine {
+            Engine::Bytes => Box::new(BytesRegexMatcher::with_scope(&pattern, scope)?),
+            Engine::Backtracking { unicode } => {
+                Box::new(FancyRegexMatcher::with_scope(&pattern, scope, multiline, unicode)?)
+            }
+            Engine::Default if multiline => Box::new(RegexMatcher::multi_line(&pattern, scope)?),
+            Engine::Default => Box::new(RegexMatcher::with_scope(&pattern, scope)?),
+        };
+        Ok(matcher)
+    };
+    let compile = |pattern: &String| {
+        compile(pattern).context(format!("Invalid regex pattern: '{}'", pattern))
+    };
+
+    if let [pattern] = patterns {
+        return compile(pattern);
+    }
+    if literal_only && (fixed_strings || patterns.iter().all(|pattern| is_pure_literal(pattern))) {
+        let matcher = MultiLiteralMatcher::new(patterns).context("Failed to build literal matcher")?;
+        return Ok(Box::new(matcher));
+    }
+    let matchers = patterns.iter().map(compile).collect::<Result<Vec<_>>>()?;
+    Ok(Box::new(OrMatcher::new(matchers)))
+}
+
+// --vimgrep 总是逐行带路径；显式的 --heading / --no-heading 优先；否则终端上分组输出，管道中逐行带路径
+fn use_heading(args: &Args) -> bool {
+    if args.vimgrep {
+        false
+    } else if args.heading {
+        true
+    } else if args.no_heading {
+        false
+    } else {
+        std::io::stdout().is_terminal()
+    }
+}
+
+// `--color auto`：只在终端上着色，`NO_COLOR`（非空）关闭自动着色；`always` 不受它影响
+fn use_color(args: &Args) -> bool {
+    match args.color {
+        ColorChoice::Never => false,
+        ColorChoice::Always => true,
+        ColorChoice::Auto => {
+            std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && std::io::stdout().is_terminal()
+        }
+    }
+}
+
+// 读取哪些规则文件：--no-ignore（或 -u）关闭全部，其余参数各关闭一种
+fn ignore_config(args: &Args) -> IgnoreConfig {
+    let no_ignore = args.no_ignore || args.unrestricted >= 1;
+    IgnoreConfig {
+        vcs: !(no_ignore || args.no_ignore_vcs),
+        dot: !(no_ignore || args.no_ignore_dot),
+    }
+}
+
+// 与 heading 相同：显式参数优先，否则终端上逐行刷新，管道中按块缓冲
+fn use_line_buffering(args: &Args) -> bool {
+    if args.line_buffered {
+        true
+    } else if args.no_line_buffered {
+        false
+    } else {
+        std::io::stdout().is_terminal()
+    }
 }
 
 fn process_paths(
-    searcher: Arc<Searcher<RegexMatcher>>, 
-    printer: Arc<Mutex<Printer>>, 
+    worker: &SearchWorker,
     paths: &[PathBuf],
-    use_parallel: bool  // 添加参数
+    use_parallel: bool,  // 添加参数
+    filter: &WalkFilter,
 ) -> Result<()> {
-    for path in paths {
-        handle_single_path(searcher.clone(), printer.clone(), path, use_parallel)?;
+    // 某个路径出错（例如不存在）时报告到标准错误并继续搜索其余路径，
+    // 最后以错误结束使退出码非零；输出管道关闭时立即停止
+    let mut failed = 0;
+    for path in dedup_paths(paths) {
+        if worker.stopped() {
+            break;
+        }
+        if let Err(e) = handle_single_path(worker, &path, use_parallel, filter) {
+            if is_broken_pipe(&e) {
+                return Err(e);
+            }
+            eprintln!("错误: {:#}", e);
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        bail!("{} of the given paths could not be searched", failed);
+    }
+    let walk_errors = worker.walk_errors.load(Ordering::Relaxed);
+    if walk_errors > 0 {
+        bail!("{} entries could not be read while walking directories", walk_errors);
     }
     Ok(())
 }
 
+/// 命令行中位于某个目录参数之内的文件参数（规范化路径），遍历目录时跳过它们
+fn explicit_files_in_dirs(paths: &[PathBuf]) -> HashSet<PathBuf> {
+    let canonical: Vec<PathBuf> = paths
+        .iter()
+        .filter_map(|path| std::fs::canonicalize(path).ok())
+        .collect();
+    canonical
+        .iter()
+        .filter(|canon| canon.is_file())
+        .filter(|canon| {
+            canonical
+                .iter()
+                .any(|dir| dir.is_dir() && canon.starts_with(dir))
+        })
+        .cloned()
+        .collect()
+}
+
+/// 去掉重复或被其他参数包含的路径，保证每个文件最多被搜索一次
+///
+/// 比较使用规范化（canonicalize）后的路径，输出时保留用户的原始写法：
+/// - 同一路径出现多次（如 `a.txt ./a.txt`）只保留第一次；
+/// - 目录位于另一个目录参数之内（如 `. src`）时只保留外层目录；
+/// - 文件位于某个目录参数之内（如 `src/main.rs src`）时两者都保留：文件按它在参数中的
+///   位置单独搜索（带文件名前缀，不受 glob / type 过滤），遍历目录时跳过它
+///   （见 `explicit_files_in_dirs`）。
+///
+/// 无法规范化的路径（通常是不存在的路径）原样保留，由后续处理报告错误。
+fn dedup_paths(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let canonical: Vec<Option<PathBuf>> = paths
+        .iter()
+        .map(|path| std::fs::canonicalize(path).ok())
+        .collect();
+
+    let covered = |i: usize, canon: &Path| {
+        canonical.iter().enumerate().any(|(j, other)| {
+            let Some(other) = other else {
+                return false;
+            };
+            if j == i {
+                return false;
+            }
+            if canon == other {
+                return j < i;
+            }
+            canon.is_dir() && other.is_dir() && canon.starts_with(other)
+        })
+    };
+
+    paths
+        .iter()
+        .zip(&canonical)
+        .enumerate()
+        .filter(|(i, (_, canon))| match canon {
+            Some(canon) => !covered(*i, canon),
+            None => true,
+        })
+        .map(|(_, (path, _))| path.clone())
+        .collect()
+}
+
 
 fn handle_single_path(
-    searcher: Arc<Searcher<RegexMatcher>>,
-    printer: Arc<Mutex<Printer>>,
+    worker: &SearchWorker,
     path: &Path,
     use_parallel: bool,
+    filter: &WalkFilter,
 ) -> Result<()> {
+    if path == Path::new(STDIN_PATH) {
+        if worker.mode == OutputMode::Hex {
+            let mut data = Vec::new();
+            std::io::stdin().lock().read_to_end(&mut data).context("Failed to read stdin")?;
+            return print_hex(worker, Path::new(STDIN_NAME), &data);
+        }
+        if worker.mode == OutputMode::Lines {
+            return stream_stdin(worker);
+        }
+        let mut stats = SearchStats::default();
+        let lines = worker.searcher.search_reader_with_stats(std::io::stdin().lock(), &mut stats)
+            .context("Failed to read stdin")?;
+        return print_results(worker, Path::new(STDIN_NAME), lines, &stats);
+    }
+
     if !path.exists() {
         bail!("File or directory not found: {}", path.display());
     }
 
-    // 确定根目录
-    let root = if path.is_file() {
-        path.parent().unwrap_or_else(|| Path::new("."))
-    } else {
-        path
-    };
-    
-    // 创建 Ignore 实例（使用根目录）
-    let ignore = Ignore::from_gitignore(root).unwrap_or_else(|_| Ignore::new(root.to_path_buf()));
-    let ignore_arc = Arc::new(Mutex::new(ignore));
-
+    // 明确给出的文件总是被搜索：忽略规则只用于遍历目录时找到的条目
     if path.is_file() {
-        {
-            let mut ignore_guard = ignore_arc.lock().unwrap();
-            if ignore_guard.should_ignore(path) {
-                // 文件被 .gitignore 忽略，静默跳过（符合 ripgrep 行为）
-                return Ok(());
-            }
-        }
-        // 对于单个文件，使用单线程版本
-        let printer_guard = printer.lock().unwrap();
-        search_file_and_print(&*searcher, &*printer_guard, path)?;
-        return Ok(());
+        return search_file_and_print(worker, path);
+    }
+
+    // 创建 Ignore 实例（以目录参数为根目录）
+    let mut ignore = Ignore::with_config(path.to_path_buf(), filter.ignore);
+    for file in &filter.ignore_files {
+        ignore
+            .add_ignore_file(file, path)
+            .with_context(|| format!("Failed to read ignore file: {}", file.display()))?;
     }
 
     if path.is_dir() {
-        // 根据参数决定使用并行还是单线程版本
-        if use_parallel {
-            walk_directory_parallel(searcher, printer, path, ignore_arc)?;
+        // 排序输出需要先收集全部文件，并按顺序逐个搜索
+        if filter.sort != SortBy::None {
+            walk_directory_sorted(worker, path, &ignore, filter)?;
+        } else if use_parallel {
+            // 根据参数决定使用并行还是单线程版本
+            walk_directory_parallel(worker, path, &ignore, filter)?;
         } else {
-            walk_directory_single_thread(searcher, printer, path, ignore_arc)?;
+            walk_directory_single_thread(worker, path, &ignore, filter)?;
         }
     }
 
@@ -108,17 +969,19 @@ fn handle_single_path(
 
 /// 单线程版本的目录遍历函数
 fn walk_directory_single_thread(
-    searcher: Arc<Searcher<RegexMatcher>>,
-    printer: Arc<Mutex<Printer>>,
+    worker: &SearchWorker,
     dir_path: &Path,
-    ignore: Arc<Mutex<Ignore>>,
+    ignore: &Ignore,
+    filter: &WalkFilter,
 ) -> Result<()> {
-    let walk_dir = WalkDir::new(dir_path)
-        .follow_links(false)
-        .into_iter();
-    
-    for entry_result in walk_dir {
-        let entry = entry_result?;
+    for entry_result in filter.walk(dir_path) {
+        let entry = match entry_result {
+            Ok(entry) => entry,
+            Err(e) => {
+                report_walk_error(worker, &e);
+                continue;
+            }
+        };
         let path = entry.path();
         
         // 显式跳过 .git 目录及其所有子项
@@ -128,26 +991,20 @@ fn walk_directory_single_thread(
         }
         
         if entry.file_type().is_file() {
+            // 单独给出的文件由它自己的参数搜索；--glob / --type 过滤
+            if filter.is_explicit_file(path) || !filter.allows_file(dir_path, path) {
+                continue;
+            }
+
             // 检查是否被忽略
-            {
-                if let Ok(mut ignore_guard) = ignore.lock() {
-                    if ignore_guard.should_ignore(path) {
-                        continue;
-                    }
-                }
+            if is_ignored(dir_path, path, ignore, filter) {
+                worker.stats.record_ignored();
+                continue;
             }
-            
-            // 搜索文件
-            let matches = match searcher.search_file(path) {
-                Ok(matches) => matches,
-                Err(_) => continue, // 跳过无法读取的文件
-            };
-            
-            // 打印结果
-            if let Ok(printer_guard) = printer.lock() {
-                for mat in matches {
-                    let _ = printer_guard.print_match(path, &mat);
-                }
+
+            search_walked_file(worker, path)?;
+            if worker.stopped() {
+                break;
             }
         }
     }
@@ -157,19 +1014,47 @@ fn walk_directory_single_thread(
 
 
 fn walk_directory_parallel(
-    searcher: Arc<Searcher<RegexMatcher>>,
-    printer: Arc<Mutex<Printer>>,
+    worker: &SearchWorker,
     dir_path: &Path,
-    ignore: Arc<Mutex<Ignore>>
+    ignore: &Ignore,
+    filter: &WalkFilter,
 ) -> Result<()> {
 
-    // 1️⃣ 收集所有需要处理的文件路径（串行）
-    // 注意：在收集阶段也需要检查 .gitignore，所以需要获取锁
-    let files: Vec<PathBuf> = WalkDir::new(dir_path)
-        .follow_links(false)
-        .into_iter()
+    // 1️⃣ 收集所有需要处理的文件路径（遍历是串行的，忽略规则的检查并行进行）
+    let files = collect_files(worker, dir_path, ignore, filter);
+
+    // 2️⃣ 并行搜索文件
+    // 注意：文件已经在收集阶段过滤过了，并行处理时不需要再检查 .gitignore
+    // 每个文件的结果在打印锁内一次性输出，不会与其他文件交错
+    files.par_iter()
+        .try_for_each(|path| search_walked_file(worker, path))
+}
+
+/// 排序版本：收集全部文件，按 --sort 指定的顺序单线程搜索
+fn walk_directory_sorted(
+    worker: &SearchWorker,
+    dir_path: &Path,
+    ignore: &Ignore,
+    filter: &WalkFilter,
+) -> Result<()> {
+    let mut files = collect_files(worker, dir_path, ignore, filter);
+    sort_files(&mut files, filter.sort);
+    for path in &files {
+        search_walked_file(worker, path)?;
+        if worker.stopped() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// 遍历目录，收集所有通过过滤规则的文件路径（保持遍历顺序）
+///
+/// 忽略规则的匹配只需要 `&Ignore`，遍历得到的候选文件在 rayon 线程池中并行检查
+fn collect_files(worker: &SearchWorker, dir_path: &Path, ignore: &Ignore, filter: &WalkFilter) -> Vec<PathBuf> {
+    let candidates: Vec<PathBuf> = filter.walk(dir_path)
         .filter_map(|entry| {
-            let entry = entry.ok()?;          // 跳过 WalkDir 错误
+            let entry = entry.map_err(|e| report_walk_error(worker, &e)).ok()?;
             let path = entry.path();
 
             // 跳过 .git 目录及其子项
@@ -183,51 +1068,210 @@ fn walk_directory_parallel(
                 return None;
             }
 
-            // .gitignore 过滤（需要获取锁，但尽量减少锁的持有时间）
-            {
-                if let Ok(mut ignore_guard) = ignore.lock() {
-                    if ignore_guard.should_ignore(path) {
-                        return None;
-                    }
-                }
+            // 单独给出的文件由它自己的参数搜索；--glob / --type 过滤
+            if filter.is_explicit_file(path) || !filter.allows_file(dir_path, path) {
+                return None;
             }
 
             Some(path.to_path_buf())
         })
         .collect();
 
-    // 2️⃣ 并行搜索文件
-    // 注意：文件已经在收集阶段过滤过了，并行处理时不需要再检查 .gitignore
-    files.par_iter()
-        .for_each(|path| {
-            // 搜索文件
-            let matches = match searcher.search_file(path) {
-                Ok(matches) => matches,
-                Err(_) => return, // 跳过无法读取的文件
-            };
-            
-            // 获取锁后打印结果
-            if let Ok(printer_guard) = printer.lock() {
-                for mat in matches {
-                    let _ = printer_guard.print_match(path, &mat);
-                }
+    candidates
+        .into_par_iter()
+        .filter(|path| {
+            let ignored = is_ignored(dir_path, path, ignore, filter);
+            if ignored {
+                worker.stats.record_ignored();
             }
-        });
-    
-    Ok(())
+            !ignored
+        })
+        .collect()
 }
 
-fn search_file_and_print(
-    searcher: &Searcher<RegexMatcher>, 
-    printer: &Printer, 
-    path: &Path
-) -> Result<()> {
-    let matches = searcher.search_file(path)
+// 遍历中无法访问的条目（例如 --follow 时悬空的链接、没有权限的目录）报告到标准错误并计数，
+// 遍历继续进行。--follow 时指向祖先目录的链接构成循环，静默跳过该链接，不算错误
+fn report_walk_error(worker: &SearchWorker, e: &walkdir::Error) {
+    if e.loop_ancestor().is_some() {
+        return;
+    }
+    eprintln!("错误: {}", e);
+    worker.walk_errors.fetch_add(1, Ordering::Relaxed);
+}
+
+// 遍历到的文件是否被忽略规则排除；被 `--glob` 明确包含的文件不受忽略规则限制
+fn is_ignored(dir_path: &Path, path: &Path, ignore: &Ignore, filter: &WalkFilter) -> bool {
+    filter.uses_ignore_rules()
+        && !filter.glob_whitelists(dir_path, path)
+        && ignore.should_ignore(path)
+}
+
+/// 搜索遍历目录时找到的文件：跳过无法读取的文件，
+/// 但输出管道已关闭时立即停止，不再搜索剩下的文件
+fn search_walked_file(worker: &SearchWorker, path: &Path) -> Result<()> {
+    // 并行搜索时其他任务已经找到匹配（`--quiet`），跳过还没开始的文件
+    if worker.stopped() {
+        return Ok(());
+    }
+    match search_file_and_print(worker, path) {
+        Err(e) if is_broken_pipe(&e) => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn search_file_and_print(worker: &SearchWorker, path: &Path) -> Result<()> {
+    // --pre：搜索预处理命令的输出，行号和偏移对应预处理后的内容
+    if let Some(pre) = &worker.pre {
+        let data = pre.output(path)?;
+        if worker.mode == OutputMode::Hex {
+            return print_hex(worker, path, &data);
+        }
+        let mut stats = SearchStats::default();
+        let lines = worker.searcher.search_reader_with_stats(data.as_slice(), &mut stats)?;
+        return print_results(worker, path, lines, &stats);
+    }
+    if worker.mode == OutputMode::Hex {
+        let data = std::fs::read(path)
+            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+        return print_hex(worker, path, &data);
+    }
+    let mut stats = SearchStats::default();
+    let lines = worker.searcher.search_file_lines_with_stats(path, &mut stats)
         .with_context(|| format!("Failed to read file: {}", path.display()))?;
+    print_results(worker, path, lines, &stats)
+}
+
+// --hex：在整个文件的字节上搜索，不分行
+fn print_hex(worker: &SearchWorker, path: &Path, data: &[u8]) -> Result<()> {
+    let matches = worker.searcher.search_bytes(data);
+    let stats = SearchStats {
+        bytes_searched: data.len() as u64,
+        lines_searched: 0,
+    };
+    worker.stats.record_file(&stats, !matches.is_empty());
+    worker.stats.record_matches(matches.len());
+    if !matches.is_empty() {
+        worker.matched.store(true, Ordering::Relaxed);
+        worker.printer.lock().unwrap().print_hex(path, data, &matches)?;
+    }
+    Ok(())
+}
+
+// 标准输入可能是不会结束的管道（`tail -f | grepdojo`）：逐行输出时每读到一批
+// 结果就立即打印并刷新，而不是等到输入结束
+fn stream_stdin(worker: &SearchWorker) -> Result<()> {
+    let path = Path::new(STDIN_NAME);
+    let mut started = false;
+    let mut stats = SearchStats::default();
+    let mut matched = false;
+    let result = worker
+        .searcher
+        .search_reader_streaming(std::io::stdin().lock(), &mut stats, |mut lines| {
+            record_matched(worker, &lines);
+            worker.stats.record_lines(&lines);
+            matched |= lines.iter().any(|line| line.kind == LineKind::Match);
+            prepare_lines(worker, &mut lines);
+            let mut printer = worker.printer.lock().unwrap();
+            if started {
+                printer.print_more_lines(path, &lines)?;
+            } else {
+                printer.print_lines(path, &lines)?;
+                started = true;
+            }
+            printer.flush()?;
+            Ok(())
+        })
+        .context("Failed to read stdin");
+    worker.stats.record_file(&stats, matched);
+    worker.printer.lock().unwrap().end_file(path)?;
+    result
+}
 
-    for mat in matches {
-        printer.print_match(path, &mat)?;
+// 记录是否有命中的行（反向匹配时是不匹配的行），用于退出码和 `--quiet`
+fn record_matched(worker: &SearchWorker, lines: &[searcher::Line]) {
+    if lines.iter().any(|line| line.kind == LineKind::Match) {
+        worker.matched.store(true, Ordering::Relaxed);
+    }
+}
+
+// 输出前处理结果行：`--replace` 替换匹配，颜色输出时补充捕获组位置。
+// 只有逐行输出时才需要；计数按搜索得到的行和匹配计算
+fn prepare_lines(worker: &SearchWorker, lines: &mut Vec<searcher::Line>) {
+    if worker.mode != OutputMode::Lines {
+        return;
+    }
+    let matcher = worker.searcher.matcher();
+    let config = worker.searcher.config();
+    match &worker.replace {
+        Some(template) if config.multiline => {
+            *lines = replace::replace_multiline(matcher, std::mem::take(lines), template, config.line_terminator());
+        }
+        Some(template) => {
+            for line in lines.iter_mut() {
+                replace::replace_line(matcher, line, template);
+            }
+        }
+        None if worker.capture_groups => {
+            for line in lines.iter_mut() {
+                highlight::attach_groups(matcher, line);
+            }
+        }
+        None => {}
+    }
+}
+
+// 文件和标准输入共用的输出流程：替换、计数汇总或逐行输出
+fn print_results(worker: &SearchWorker, path: &Path, mut lines: Vec<searcher::Line>, stats: &SearchStats) -> Result<()> {
+    worker.stats.record_file(stats, lines.iter().any(|line| line.kind == LineKind::Match));
+    worker.stats.record_lines(&lines);
+    // 没有匹配的文件才是结果；退出码取决于是否输出了路径
+    if worker.mode == OutputMode::FilesWithoutMatch {
+        if !lines.iter().any(|line| line.kind == LineKind::Match) {
+            worker.matched.store(true, Ordering::Relaxed);
+            worker.printer.lock().unwrap().print_path(path)?;
+        }
+        return Ok(());
+    }
+    record_matched(worker, &lines);
+    prepare_lines(worker, &mut lines);
+
+    // 计数模式在这里按文件汇总，打印器只负责输出结果
+    let count = match worker.mode {
+        OutputMode::Lines => {
+            let mut printer = worker.printer.lock().unwrap();
+            printer.print_lines(path, &lines)?;
+            printer.end_file(path)?;
+            return Ok(());
+        }
+        OutputMode::Count => {
+            let count = lines.iter().filter(|line| line.kind == LineKind::Match).count();
+            // 替换预览：每个匹配行都会被替换，即使替换结果与原文相同
+            if worker.replace.is_some() && count > 0 {
+                worker.changed_lines.fetch_add(count, Ordering::Relaxed);
+                worker.changed_files.fetch_add(1, Ordering::Relaxed);
+            }
+            count
+        }
+        OutputMode::Hex => unreachable!("--hex 按字节搜索，不经过逐行输出"),
+        OutputMode::FilesWithoutMatch => unreachable!("在上面按文件处理"),
+        OutputMode::Quiet => return Ok(()),
+        OutputMode::FilesWithMatches => {
+            if lines.iter().any(|line| line.kind == LineKind::Match) {
+                worker.printer.lock().unwrap().print_path(path)?;
+            }
+            return Ok(());
+        }
+        OutputMode::CountMatches => lines
+            .iter()
+            .filter(|line| line.kind == LineKind::Match)
+            .map(|line| line.matches.len())
+            .sum(),
+    };
+    if worker.total {
+        worker.total_count.fetch_add(count, Ordering::Relaxed);
+    } else if count > 0 {
+        worker.printer.lock().unwrap().print_count(path, count)?;
     }
     
     Ok(())
-}
\ No newline at end of file
+}
@@ -3,8 +3,13 @@ use core::run;
 use std::process;
 
 fn main() {
-    if let Err(e) = run() {
-        eprintln!("Error: {}", e);
-        process::exit(1);
+    // 与 grep 一致：找到匹配为 0，没有匹配为 1，出错为 2
+    match run() {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(2);
+        }
     }
-}
\ No newline at end of file
+}
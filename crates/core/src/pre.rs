@@ -0,0 +1,120 @@
+//! `--pre`：搜索前用外部命令预处理文件（例如解压、提取文本），搜索的是命令的标准输出
+//!
+//! 指定缓存目录时，命令的输出按“命令 + 路径 + 修改时间 + 大小”缓存在磁盘上，
+//! 文件没有变化时重复搜索（例如在 watch 循环中）不再重新运行命令。
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::UNIX_EPOCH;
+use anyhow::{Context, Result, bail};
+
+/// 缓存目录的总大小上限，超过后从最早写入的条目开始删除
+const CACHE_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// 预处理命令，以及可选的输出缓存
+pub struct Preprocessor {
+    command: String,
+    cache_dir: Option<PathBuf>,
+}
+
+impl Preprocessor {
+    pub fn new(command: String, cache_dir: Option<PathBuf>) -> Self {
+        Preprocessor { command, cache_dir }
+    }
+
+    /// 返回预处理后的内容：缓存命中时直接读取，否则运行命令（并写入缓存）
+    pub fn output(&self, path: &Path) -> Result<Vec<u8>> {
+        let entry = self.cache_entry(path);
+        if let Some(entry) = &entry
+            && let Ok(data) = fs::read(entry)
+        {
+            return Ok(data);
+        }
+
+        let data = self.run(path)?;
+        // 缓存只是优化：写入失败不影响搜索
+        if let Some(entry) = &entry {
+            let _ = self.store(entry, &data);
+        }
+        Ok(data)
+    }
+
+    // 以文件路径为唯一参数运行命令，收集标准输出；命令的标准错误直接透传
+    fn run(&self, path: &Path) -> Result<Vec<u8>> {
+        let output = Command::new(&self.command)
+            .arg(path)
+            .stdin(Stdio::null())
+            .stderr(Stdio::inherit())
+            .output()
+            .with_context(|| format!("Failed to run preprocessor '{}'", self.command))?;
+        if !output.status.success() {
+            bail!(
+                "preprocessor '{}' failed on {} ({})",
+                self.command,
+                path.display(),
+                output.status
+            );
+        }
+        Ok(output.stdout)
+    }
+
+    // 缓存条目的位置；没有缓存目录或无法读取文件元数据时不使用缓存。
+    // 键使用标准库的 SipHash（固定密钥），同一个程序版本中多次运行结果一致
+    fn cache_entry(&self, path: &Path) -> Option<PathBuf> {
+        let dir = self.cache_dir.as_ref()?;
+        let metadata = fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        let path = fs::canonicalize(path).ok()?;
+
+        let mut hasher = DefaultHasher::new();
+        self.command.hash(&mut hasher);
+        path.hash(&mut hasher);
+        modified.hash(&mut hasher);
+        metadata.len().hash(&mut hasher);
+        Some(dir.join(format!("{:016x}", hasher.finish())))
+    }
+
+    // 先写临时文件再改名，并发的搜索任务不会读到写了一半的条目
+    fn store(&self, entry: &Path, data: &[u8]) -> io::Result<()> {
+        let Some(dir) = entry.parent() else {
+            return Ok(());
+        };
+        fs::create_dir_all(dir)?;
+        let tmp = entry.with_extension(format!("tmp{}", std::process::id()));
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, entry)?;
+        evict(dir)
+    }
+}
+
+// 缓存总大小超过上限时，按写入时间从旧到新删除条目
+fn evict(dir: &Path) -> io::Result<()> {
+    let mut entries = Vec::new();
+    let mut total = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() {
+            continue;
+        }
+        total += metadata.len();
+        entries.push((metadata.modified().unwrap_or(UNIX_EPOCH), metadata.len(), entry.path()));
+    }
+    if total <= CACHE_MAX_BYTES {
+        return Ok(());
+    }
+    entries.sort();
+    for (_, len, path) in entries {
+        if total <= CACHE_MAX_BYTES {
+            break;
+        }
+        if fs::remove_file(&path).is_ok() {
+            total -= len;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,93 @@
+//! `--replace`：把匹配行中的每个匹配替换为展开后的模板
+
+use matcher::{Match, Matcher};
+use searcher::{Line, LineKind};
+
+/// 替换一行中的所有匹配
+///
+/// 替换后匹配的位置指向新文本中的替换结果，`-o` 因此会输出替换后的文本。
+/// 上下文行保持不变。
+pub fn replace_line<M: Matcher>(matcher: &M, line: &mut Line, template: &str) {
+    if line.kind != LineKind::Match {
+        return;
+    }
+    let (replaced, spans) = replace_text(matcher, &line.content, template);
+    line.matches = to_matches(spans, line.number, &replaced);
+    line.content = replaced;
+}
+
+/// `-U`：匹配可能跨越多行，逐行替换找不到这样的匹配
+///
+/// 相邻的匹配行连同行结束符拼接成一段文本，在整段文本上替换，结果作为一行输出
+/// （内容中保留换行），行号和偏移取这段文本的第一行。上下文行保持不变。
+pub fn replace_multiline<M: Matcher>(matcher: &M, lines: Vec<Line>, template: &str, terminator: u8) -> Vec<Line> {
+    let mut result = Vec::with_capacity(lines.len());
+    let mut group: Vec<Line> = Vec::new();
+    for line in lines {
+        // 反向匹配命中的行没有匹配，不参与拼接
+        let matched = line.kind == LineKind::Match && !line.matches.is_empty();
+        let adjacent = group.last().is_some_and(|last| last.number + 1 == line.number);
+        if !(matched && adjacent) {
+            result.extend(replace_group(matcher, &mut group, template, terminator));
+        }
+        if matched {
+            group.push(line);
+        } else {
+            result.push(line);
+        }
+    }
+    result.extend(replace_group(matcher, &mut group, template, terminator));
+    result
+}
+
+// 替换一组相邻的匹配行，清空 group
+fn replace_group<M: Matcher>(matcher: &M, group: &mut Vec<Line>, template: &str, terminator: u8) -> Option<Line> {
+    let first = group.first()?;
+    let (number, offset) = (first.number, first.offset);
+    // 每行都带上行结束符，匹配到行尾换行的模式（`foo\n`）同样能找到
+    let mut text = String::new();
+    for line in group.drain(..) {
+        text.push_str(&line.content);
+        text.push(terminator as char);
+    }
+    let (mut replaced, mut spans) = replace_text(matcher, &text, template);
+    // 打印器自己输出行结束符
+    if replaced.ends_with(terminator as char) {
+        replaced.pop();
+        for span in &mut spans {
+            span.0 = span.0.min(replaced.len());
+            span.1 = span.1.min(replaced.len());
+        }
+    }
+    Some(Line {
+        number,
+        offset,
+        matches: to_matches(spans, number, &replaced),
+        content: replaced,
+        kind: LineKind::Match,
+    })
+}
+
+// 替换 text 中的所有匹配，返回新文本和每个替换结果在其中的范围
+fn replace_text<M: Matcher>(matcher: &M, text: &str, template: &str) -> (String, Vec<(usize, usize)>) {
+    let mut replaced = String::with_capacity(text.len());
+    let mut spans = Vec::new();
+    let mut last_end = 0;
+    for caps in matcher.captures(text) {
+        let (start, end) = caps.span();
+        replaced.push_str(&text[last_end..start]);
+        let new_start = replaced.len();
+        caps.expand(text, template, &mut replaced);
+        spans.push((new_start, replaced.len()));
+        last_end = end;
+    }
+    replaced.push_str(&text[last_end..]);
+    (replaced, spans)
+}
+
+fn to_matches(spans: Vec<(usize, usize)>, number: usize, content: &str) -> Vec<Match> {
+    spans
+        .into_iter()
+        .map(|(start, end)| Match::new(start, end, number, content.to_string()))
+        .collect()
+}
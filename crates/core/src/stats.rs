@@ -0,0 +1,71 @@
+//! `--stats`：整次运行的统计，所有搜索任务（包括 rayon 工作线程）共享
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Instant;
+use searcher::{Line, LineKind, SearchStats};
+
+pub struct RunStats {
+    started: Instant,
+    files_searched: AtomicUsize,
+    files_matched: AtomicUsize,
+    files_ignored: AtomicUsize,
+    matched_lines: AtomicUsize,
+    matches: AtomicUsize,
+    bytes_searched: AtomicU64,
+}
+
+impl RunStats {
+    pub fn new() -> Self {
+        RunStats {
+            started: Instant::now(),
+            files_searched: AtomicUsize::new(0),
+            files_matched: AtomicUsize::new(0),
+            files_ignored: AtomicUsize::new(0),
+            matched_lines: AtomicUsize::new(0),
+            matches: AtomicUsize::new(0),
+            bytes_searched: AtomicU64::new(0),
+        }
+    }
+
+    /// 搜索完一个文件（或标准输入）：`stats` 是搜索器统计的数据量，`matched` 表示是否有命中
+    pub fn record_file(&self, stats: &SearchStats, matched: bool) {
+        self.files_searched.fetch_add(1, Ordering::Relaxed);
+        self.bytes_searched.fetch_add(stats.bytes_searched, Ordering::Relaxed);
+        if matched {
+            self.files_matched.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// 一个文件被 .gitignore 规则跳过
+    pub fn record_ignored(&self) {
+        self.files_ignored.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 累加结果行中的命中行数和匹配数（流式搜索时每批调用一次）
+    pub fn record_lines(&self, lines: &[Line]) {
+        for line in lines.iter().filter(|line| line.kind == LineKind::Match) {
+            self.matched_lines.fetch_add(1, Ordering::Relaxed);
+            self.matches.fetch_add(line.matches.len(), Ordering::Relaxed);
+        }
+    }
+
+    /// `--hex` 没有行，只累加匹配数
+    pub fn record_matches(&self, matches: usize) {
+        self.matches.fetch_add(matches, Ordering::Relaxed);
+    }
+
+    /// 输出的汇总行，第一行为空行，与前面的搜索结果隔开
+    pub fn summary(&self) -> Vec<String> {
+        let load = |counter: &AtomicUsize| counter.load(Ordering::Relaxed);
+        vec![
+            String::new(),
+            format!("{} matched lines", load(&self.matched_lines)),
+            format!("{} matches", load(&self.matches)),
+            format!("{} files contained matches", load(&self.files_matched)),
+            format!("{} files searched", load(&self.files_searched)),
+            format!("{} files skipped by ignore rules", load(&self.files_ignored)),
+            format!("{} bytes searched", self.bytes_searched.load(Ordering::Relaxed)),
+            format!("{:.6} seconds", self.started.elapsed().as_secs_f64()),
+        ]
+    }
+}
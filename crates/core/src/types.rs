@@ -0,0 +1,125 @@
+//! 文件类型过滤（`--type` / `--type-not`）
+
+use std::collections::BTreeMap;
+use anyhow::{Result, bail};
+
+use ignore::GlobMatcher;
+
+/// 内置的文件类型表：类型名 -> 文件名 glob
+const DEFAULT_TYPES: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    ("cpp", &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx"]),
+    ("css", &["*.css"]),
+    ("go", &["*.go"]),
+    ("html", &["*.html", "*.htm"]),
+    ("java", &["*.java"]),
+    ("js", &["*.js", "*.mjs", "*.cjs", "*.jsx"]),
+    ("json", &["*.json"]),
+    ("md", &["*.md", "*.markdown"]),
+    ("py", &["*.py", "*.pyi"]),
+    ("rust", &["*.rs"]),
+    ("sh", &["*.sh", "*.bash", "*.zsh"]),
+    ("toml", &["*.toml", "Cargo.lock"]),
+    ("ts", &["*.ts", "*.tsx"]),
+    ("txt", &["*.txt"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+];
+
+/// 文件类型定义
+#[derive(Debug, Clone)]
+pub struct TypeDefs {
+    defs: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for TypeDefs {
+    fn default() -> Self {
+        let defs = DEFAULT_TYPES
+            .iter()
+            .map(|(name, globs)| {
+                (name.to_string(), globs.iter().map(|g| g.to_string()).collect())
+            })
+            .collect();
+        TypeDefs { defs }
+    }
+}
+
+impl TypeDefs {
+    /// 添加自定义类型（`--type-add`），格式为 `name:glob`，多个 glob 用 `,` 分隔。
+    /// 类型已经存在时追加到原有定义之后
+    pub fn add(&mut self, spec: &str) -> Result<()> {
+        let Some((name, globs)) = spec.split_once(':') else {
+            bail!("invalid type definition '{}': expected name:glob", spec);
+        };
+        let name = name.trim();
+        if name.is_empty() || globs.trim().is_empty() {
+            bail!("invalid type definition '{}': expected name:glob", spec);
+        }
+        self.defs
+            .entry(name.to_string())
+            .or_default()
+            .extend(globs.split(',').map(|glob| glob.trim().to_string()));
+        Ok(())
+    }
+
+    /// 清空类型的全部 glob（`--type-clear`），之后可以用 `--type-add` 重新定义
+    pub fn clear(&mut self, name: &str) {
+        if let Some(globs) = self.defs.get_mut(name) {
+            globs.clear();
+        }
+    }
+
+    /// 根据 `--type` 选中和 `--type-not` 排除的类型名构建过滤器，未知的类型名返回错误
+    pub fn select(&self, names: &[String], excluded: &[String]) -> Result<TypeFilter> {
+        Ok(TypeFilter {
+            selected: !names.is_empty(),
+            globs: self.globs(names)?,
+            excluded: self.globs(excluded)?,
+        })
+    }
+
+    // 多个类型的全部 glob
+    fn globs(&self, names: &[String]) -> Result<Vec<GlobMatcher>> {
+        let mut globs = Vec::new();
+        for name in names {
+            match self.defs.get(name) {
+                Some(defs) => globs.extend(defs.iter().map(|glob| GlobMatcher::new(glob))),
+                None => bail!("unrecognized file type: {}", name),
+            }
+        }
+        Ok(globs)
+    }
+
+    /// `--type-list` 的输出：每个类型一行，`名字: glob, glob`，按名字排序；
+    /// 被 `--type-clear` 清空的类型不列出
+    pub fn list(&self) -> Vec<String> {
+        self.defs
+            .iter()
+            .filter(|(_, globs)| !globs.is_empty())
+            .map(|(name, globs)| format!("{}: {}", name, globs.join(", ")))
+            .collect()
+    }
+}
+
+/// 选中和排除的文件类型，按文件名匹配
+#[derive(Debug, Clone, Default)]
+pub struct TypeFilter {
+    // 是否指定了 --type；选中的类型被 --type-clear 清空后不匹配任何文件
+    selected: bool,
+    globs: Vec<GlobMatcher>,
+    // --type-not 排除的类型
+    excluded: Vec<GlobMatcher>,
+}
+
+impl TypeFilter {
+    /// 没有指定任何 --type / --type-not，不做过滤
+    pub fn is_empty(&self) -> bool {
+        !self.selected && self.excluded.is_empty()
+    }
+
+    /// 文件是否通过类型过滤：指定了 --type 时必须属于选中的任一类型，
+    /// 并且不属于 --type-not 排除的任何类型（排除优先）
+    pub fn matches(&self, file_name: &str) -> bool {
+        let is_match = |globs: &[GlobMatcher]| globs.iter().any(|glob| glob.is_match(file_name));
+        (!self.selected || is_match(&self.globs)) && !is_match(&self.excluded)
+    }
+}
@@ -0,0 +1,358 @@
+//! 目录遍历时的路径过滤
+
+use std::collections::HashSet;
+use std::fs::Metadata;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use walkdir::{DirEntry, WalkDir};
+use ignore::{GlobMatcher, IgnoreConfig};
+
+use crate::types::TypeFilter;
+
+/// `--glob` 指定的单条规则，`!` 开头表示排除，`/` 结尾表示只匹配目录
+///
+/// 锚定规则与 gitignore 相同：
+/// - 不含 `/` 的模式（如 `foo`）匹配任意深度的文件名或目录名；
+/// - 以 `/` 开头（如 `/foo`）或中间含 `/`（如 `src/*.rs`）的模式
+///   锚定在搜索根目录，匹配相对于根目录的完整路径。
+#[derive(Debug, Clone)]
+pub struct GlobRule {
+    glob: GlobMatcher,
+    negated: bool,
+    dir_only: bool,
+    anchored: bool,
+}
+
+impl GlobRule {
+    pub fn new(glob: &str) -> Self {
+        let (negated, pattern) = match glob.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, glob),
+        };
+        let (dir_only, pattern) = match pattern.strip_suffix('/') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        let anchored = pattern.contains('/');
+        GlobRule {
+            // 开头的 `/` 只表示锚定，不参与匹配
+            glob: GlobMatcher::new(pattern.strip_prefix('/').unwrap_or(pattern)),
+            negated,
+            dir_only,
+            anchored,
+        }
+    }
+
+    // 锚定的模式匹配相对于搜索根目录的路径，其余模式匹配文件名；
+    // 只匹配目录的规则永远不会命中文件
+    fn matches(&self, relative: &str, file_name: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        if self.anchored {
+            self.glob.is_match(relative)
+        } else {
+            self.glob.is_match(file_name)
+        }
+    }
+}
+
+/// 命令行指定的遍历过滤规则
+///
+/// 文件的判定顺序（与 ripgrep 一致）：
+/// 1. `--glob`：最后一条命中的规则决定结果；存在非 `!` 规则时，
+///    一条都没命中的文件被排除。只要 glob 给出了结论，`--type` 就不再参与；
+/// 2. `--type` / `--type-not`：只有 glob 没有结论时才生效，文件必须属于任一选中的类型，
+///    并且不属于任何排除的类型。
+///
+/// 目录只受 `!` 规则影响：最后命中的规则是 `!` 规则时整个子树被剪掉。
+/// 以 `/` 结尾的规则只匹配目录（`-g '!build/'` 不会排除名为 `build` 的文件）；
+/// 非 `!` 的目录规则（如 `-g 'build/'`）表示包含该目录下的所有文件。
+#[derive(Debug, Clone, Default)]
+pub struct WalkFilter {
+    /// 目录白名单（glob，匹配目录名）。为空表示不限制；
+    /// 多个模式取并集，命中目录下的所有内容都会被搜索
+    pub include_dirs: Vec<GlobMatcher>,
+    pub globs: Vec<GlobRule>,
+    pub types: TypeFilter,
+    /// 最大遍历深度（根目录的直接子项深度为 1）
+    pub max_depth: Option<usize>,
+    /// 跟随符号链接（`--follow`）。深度按链接所在的位置计算，与链接指向哪里无关；
+    /// 指向祖先目录的链接（循环）产生 `loop_ancestor` 错误，同一个目录经由不同链接
+    /// 到达多次时只遍历第一次，因此既不会无限深入，也不会重复输出文件
+    pub follow: bool,
+    /// 输出的文件顺序
+    pub sort: SortBy,
+    /// 读取哪些目录中的规则文件（`.gitignore`、`.ignore`）
+    pub ignore: IgnoreConfig,
+    /// `--ignore-file` 指定的额外规则文件，规则相对于每个搜索根目录
+    pub ignore_files: Vec<PathBuf>,
+    /// 搜索隐藏文件和目录（名字以 `.` 开头）；关闭时整个隐藏目录被剪掉
+    pub hidden: bool,
+    /// 命令行中单独给出、同时又位于某个目录参数之内的文件（规范化路径）。
+    /// 它们按自己的参数单独搜索，遍历目录时跳过，保证每个文件只搜索一次
+    pub explicit_files: HashSet<PathBuf>,
+}
+
+impl WalkFilter {
+    /// 按过滤规则遍历目录：深度限制和目录白名单在遍历时直接剪枝
+    pub fn walk<'a>(&'a self, root: &'a Path) -> impl Iterator<Item = walkdir::Result<DirEntry>> + 'a {
+        let max_depth = self.max_depth.unwrap_or(usize::MAX);
+        // 跟随链接时已经进入过的目录（规范化路径）
+        let mut visited = HashSet::new();
+        WalkDir::new(root)
+            .follow_links(self.follow)
+            .max_depth(max_depth)
+            .into_iter()
+            .filter_entry(move |entry| {
+                self.allows_entry(root, entry) && (!self.follow || first_visit(&mut visited, entry, max_depth))
+            })
+    }
+
+    /// 用于 `WalkDir::filter_entry`：返回 false 时整个子树都会被剪掉
+    pub fn allows_entry(&self, root: &Path, entry: &DirEntry) -> bool {
+        // 搜索根目录本身永远不会被剪掉
+        if entry.depth() == 0 {
+            return true;
+        }
+        if !self.hidden && entry.file_name().to_string_lossy().starts_with('.') {
+            return false;
+        }
+        if !entry.file_type().is_dir() {
+            return true;
+        }
+        self.allows_dir(root, entry.path())
+    }
+
+    /// 是否需要按忽略规则过滤文件：读取任意规则文件或指定了 `--ignore-file`
+    pub fn uses_ignore_rules(&self) -> bool {
+        self.ignore.any() || !self.ignore_files.is_empty()
+    }
+
+    /// 遍历到的文件是否已经作为单独的参数给出（由该参数负责搜索）
+    pub fn is_explicit_file(&self, path: &Path) -> bool {
+        !self.explicit_files.is_empty()
+            && std::fs::canonicalize(path).is_ok_and(|canon| self.explicit_files.contains(&canon))
+    }
+
+    /// 判断遍历到的文件是否需要搜索（`--glob` 优先于 `--type`）
+    ///
+    /// 两种遍历方式都在搜索之前调用，被排除的文件不会被读取，因此计数等
+    /// 所有输出模式都只包含通过过滤的文件。命令行直接给出的文件不经过这里
+    pub fn allows_file(&self, root: &Path, path: &Path) -> bool {
+        let (relative, file_name) = relative_and_name(root, path);
+        if let Some(decision) = self.glob_decision(&relative, &file_name) {
+            return decision;
+        }
+        self.types.is_empty() || self.types.matches(&file_name)
+    }
+
+    /// 文件是否被 `--glob` 明确包含：最后命中的规则不是 `!` 规则，或者文件位于
+    /// 被 `dir/` 规则包含的目录中。与 ripgrep 一致，明确包含的文件不受 .gitignore
+    /// 限制，例如 `-g 'target/debug/important.log'` 可以搜索被 `target/` 忽略的文件
+    pub fn glob_whitelists(&self, root: &Path, path: &Path) -> bool {
+        let (relative, file_name) = relative_and_name(root, path);
+        match self.last_matching_rule(&relative, &file_name, false) {
+            Some(rule) => !rule.negated,
+            None => self.inside_included_dir(&relative),
+        }
+    }
+
+    // glob 对文件的结论：Some(true) 包含，Some(false) 排除，None 表示没有意见
+    fn glob_decision(&self, relative: &str, file_name: &str) -> Option<bool> {
+        if let Some(rule) = self.last_matching_rule(relative, file_name, false) {
+            return Some(!rule.negated);
+        }
+        // 位于被 `dir/` 规则包含的目录之内
+        if self.inside_included_dir(relative) {
+            return Some(true);
+        }
+        if self.globs.iter().any(|rule| !rule.negated) {
+            return Some(false);
+        }
+        None
+    }
+
+    fn last_matching_rule(&self, relative: &str, name: &str, is_dir: bool) -> Option<&GlobRule> {
+        self.globs
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(relative, name, is_dir))
+    }
+
+    // 文件的某一级祖先目录是否被非 `!` 的目录规则命中
+    fn inside_included_dir(&self, relative: &str) -> bool {
+        if !self.globs.iter().any(|rule| rule.dir_only && !rule.negated) {
+            return false;
+        }
+        let mut ancestor_end = 0;
+        while let Some(offset) = relative[ancestor_end..].find('/') {
+            ancestor_end += offset;
+            let ancestor = &relative[..ancestor_end];
+            let name = ancestor.rsplit('/').next().unwrap_or(ancestor);
+            if self
+                .last_matching_rule(ancestor, name, true)
+                .is_some_and(|rule| rule.dir_only && !rule.negated)
+            {
+                return true;
+            }
+            ancestor_end += 1;
+        }
+        false
+    }
+
+    fn allows_dir(&self, root: &Path, dir: &Path) -> bool {
+        let relative = dir.strip_prefix(root).unwrap_or(dir);
+
+        // 最后命中的 glob 是 `!` 规则时剪掉整个目录
+        let relative_str = relative.to_string_lossy().replace('\\', "/");
+        let name = dir
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        if self
+            .last_matching_rule(&relative_str, &name, true)
+            .is_some_and(|rule| rule.negated)
+        {
+            return false;
+        }
+
+        if self.include_dirs.is_empty() {
+            return true;
+        }
+        // 目录自身或任意一级祖先（相对于根目录）命中白名单即可进入
+        relative.components().any(|component| {
+            let name = component.as_os_str().to_string_lossy();
+            self.include_dirs.iter().any(|glob| glob.is_match(&name))
+        })
+    }
+}
+
+// 目录是否第一次被访问；文件以及无法规范化的目录总是放行。
+// 位于深度上限的目录不会被展开，不计为访问过，以免它挡住之后经由更浅的路径到达的同一目录
+fn first_visit(visited: &mut HashSet<PathBuf>, entry: &DirEntry, max_depth: usize) -> bool {
+    if !entry.file_type().is_dir() || entry.depth() >= max_depth {
+        return true;
+    }
+    match std::fs::canonicalize(entry.path()) {
+        Ok(canon) => visited.insert(canon),
+        Err(_) => true,
+    }
+}
+
+// 相对于搜索根目录的路径（统一使用 `/`）以及文件名
+fn relative_and_name(root: &Path, path: &Path) -> (String, String) {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let relative = relative.to_string_lossy().replace('\\', "/");
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    (relative, file_name)
+}
+
+/// `--sort` 的排序键
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SortBy {
+    /// 不排序（遍历顺序，可并行）
+    #[default]
+    None,
+    /// 按路径逐段比较（目录中的文件与同级的文件按名字交错排列）
+    Path,
+    /// 按最后修改时间
+    Modified,
+    /// 按最后访问时间
+    Accessed,
+    /// 按创建时间
+    Created,
+}
+
+/// 按排序键对文件排序
+///
+/// 路径按组件逐段比较，每一段按字节的字典序：`a/z.txt` 排在 `b/a.txt` 之前，
+/// 目录 `a` 中的所有文件排在 `a-b.txt`、`a.txt` 之前（组件 `a` 小于 `a-b.txt`），
+/// 大写字母排在小写字母之前。
+///
+/// 时间戳按从旧到新排列；平台不支持或读取失败的文件排在最后。
+/// 时间相同（或都缺失）时按路径排序，保证结果稳定。
+pub fn sort_files(files: &mut [PathBuf], sort: SortBy) {
+    let timestamp: fn(&Metadata) -> io::Result<SystemTime> = match sort {
+        SortBy::None => return,
+        SortBy::Path => {
+            // 按组件比较而不是按整个字符串比较：`a/z.txt` 与 `a.txt` 比较的是 `a` 和 `a.txt`
+            files.sort_by(|a, b| a.components().cmp(b.components()));
+            return;
+        }
+        SortBy::Modified => Metadata::modified,
+        SortBy::Accessed => Metadata::accessed,
+        SortBy::Created => Metadata::created,
+    };
+    files.sort_by_cached_key(|path| {
+        let time = std::fs::metadata(path).and_then(|m| timestamp(&m)).ok();
+        (time.is_none(), time, path.clone())
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File, FileTimes};
+    use std::time::Duration;
+
+    #[test]
+    fn sort_path_compares_components() {
+        let mut files: Vec<PathBuf> = ["a.txt", "b/a.txt", "a-b.txt", "B.txt", "a/z.txt", "A/x.txt"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        sort_files(&mut files, SortBy::Path);
+        let sorted: Vec<&str> = files.iter().map(|path| path.to_str().unwrap()).collect();
+        assert_eq!(sorted, ["A/x.txt", "B.txt", "a/z.txt", "a-b.txt", "a.txt", "b/a.txt"]);
+    }
+
+    #[test]
+    fn sort_none_keeps_walk_order() {
+        let mut files = vec![PathBuf::from("b"), PathBuf::from("a")];
+        sort_files(&mut files, SortBy::None);
+        assert_eq!(files, [PathBuf::from("b"), PathBuf::from("a")]);
+    }
+
+    fn names(files: &[PathBuf]) -> Vec<&str> {
+        files.iter().map(|path| path.file_name().unwrap().to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn sort_accessed_orders_by_access_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        for (name, secs) in [("a", 30), ("b", 10), ("c", 20)] {
+            let file = File::create(dir.path().join(name)).unwrap();
+            let times = FileTimes::new().set_accessed(base + Duration::from_secs(secs));
+            file.set_times(times).unwrap();
+        }
+        // 读取不到时间戳的文件排在最后，相互之间按路径排序
+        let mut files: Vec<PathBuf> = ["z-missing", "a", "y-missing", "c", "b"]
+            .iter()
+            .map(|name| dir.path().join(name))
+            .collect();
+        sort_files(&mut files, SortBy::Accessed);
+        assert_eq!(names(&files), ["b", "c", "a", "y-missing", "z-missing"]);
+    }
+
+    #[test]
+    fn sort_created_orders_by_creation_time() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["c", "a", "b"] {
+            fs::write(dir.path().join(name), "").unwrap();
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        // 平台或文件系统不记录创建时间时无法比较
+        if fs::metadata(dir.path().join("a")).and_then(|m| m.created()).is_err() {
+            return;
+        }
+        let mut files: Vec<PathBuf> = ["a", "b", "c"].iter().map(|name| dir.path().join(name)).collect();
+        sort_files(&mut files, SortBy::Created);
+        assert_eq!(names(&files), ["c", "a", "b"]);
+    }
+}
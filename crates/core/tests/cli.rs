@@ -0,0 +1,1049 @@
+//! 命令行的端到端测试：在临时目录中运行编译好的 `grepdojo`
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use tempfile::TempDir;
+
+// 在 `dir` 中运行 grepdojo，标准输入为空，不读取用户的配置文件
+fn run(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_core"))
+        .args(args)
+        .current_dir(dir)
+        .env_remove("GREPDOJO_CONFIG")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+// 与 `run` 相同，但从标准输入读入 `input`
+fn run_with_input(dir: &Path, args: &[&str], input: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_core"))
+        .args(args)
+        .current_dir(dir)
+        .env_remove("GREPDOJO_CONFIG")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+// 标准输出（按行排序，不受并行搜索的顺序影响）和退出码
+fn sorted_stdout(dir: &Path, args: &[&str]) -> (Vec<String>, Option<i32>) {
+    let output = run(dir, args);
+    let mut lines: Vec<String> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(String::from)
+        .collect();
+    lines.sort();
+    (lines, output.status.code())
+}
+
+// 创建临时目录并写入文件；路径中的目录会自动创建
+fn tree(files: &[(&str, &str)]) -> TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    for (path, content) in files {
+        let path = dir.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn invert_match() {
+    let dir = tree(&[("a.txt", "foo\nbar\nfoo foo\nbaz\n")]);
+    assert_eq!(
+        sorted_stdout(dir.path(), &["-v", "foo", "a.txt"]),
+        (vec!["a.txt:2:bar".to_string(), "a.txt:4:baz".to_string()], Some(0))
+    );
+    assert_eq!(sorted_stdout(dir.path(), &["-v", "-c", "foo", "a.txt"]).0, ["a.txt:2"]);
+    // 每一行都匹配时没有命中的行
+    assert_eq!(sorted_stdout(dir.path(), &["-v", "", "a.txt"]), (vec![], Some(1)));
+}
+
+#[test]
+fn count_per_file() {
+    let dir = tree(&[("a.txt", "foo\nbar\nfoo foo\n"), ("b.txt", "baz\n"), ("c.txt", "foo\n")]);
+    // 没有匹配的文件不输出
+    assert_eq!(
+        sorted_stdout(dir.path(), &["-c", "foo", "."]),
+        (vec!["./a.txt:2".to_string(), "./c.txt:1".to_string()], Some(0))
+    );
+    assert_eq!(sorted_stdout(dir.path(), &["--count-matches", "foo", "a.txt"]).0, ["a.txt:3"]);
+    assert_eq!(sorted_stdout(dir.path(), &["-c", "nomatch", "."]), (vec![], Some(1)));
+}
+
+#[test]
+fn quiet_exit_codes() {
+    let dir = tree(&[("a.txt", "foo\n"), ("b.txt", "bar\n")]);
+    for jobs in ["-j1", "-j4"] {
+        let output = run(dir.path(), &[jobs, "-q", "foo", "."]);
+        assert!(output.stdout.is_empty());
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(run(dir.path(), &[jobs, "-q", "nomatch", "."]).status.code(), Some(1));
+        // 找到匹配时即使其他路径出错也以 0 退出；没有匹配时错误决定退出码
+        assert_eq!(run(dir.path(), &[jobs, "-q", "foo", "missing", "."]).status.code(), Some(0));
+        assert_eq!(run(dir.path(), &[jobs, "-q", "foo", "missing"]).status.code(), Some(2));
+    }
+}
+
+#[test]
+fn only_matching() {
+    let dir = tree(&[("a.txt", "foo\nbar\nfoo fooo\n")]);
+    let output = run(dir.path(), &["-o", "fo+", "a.txt"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "a.txt:1:foo\na.txt:3:foo\na.txt:3:fooo\n");
+}
+
+#[test]
+fn byte_offsets() {
+    let dir = tree(&[("a.txt", "foo\nbar\nfoo foo\n")]);
+    let output = run(dir.path(), &["-b", "foo", "a.txt"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "a.txt:1:0:foo\na.txt:3:8:foo foo\n");
+    let output = run(dir.path(), &["-b", "-o", "foo", "a.txt"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "a.txt:1:0:foo\na.txt:3:8:foo\na.txt:3:12:foo\n"
+    );
+
+    // 大文件（mmap）中的偏移同样是相对于整个文件的
+    let filler = "x".repeat(99) + "\n";
+    let big = filler.repeat(3000) + "foo\n";
+    fs::write(dir.path().join("big.txt"), &big).unwrap();
+    let output = run(dir.path(), &["-b", "foo", "big.txt"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "big.txt:3001:300000:foo\n");
+}
+
+#[test]
+fn heading_groups_lines_per_file() {
+    let dir = tree(&[("a.txt", "foo\nbar\nfoo foo\n"), ("b.txt", "baz\n"), ("c.txt", "foo\n")]);
+    // 并行搜索时每个文件的分组仍然完整，不与其他文件交错
+    for jobs in ["-j1", "-j4"] {
+        let output = run(dir.path(), &[jobs, "--heading", "foo", "."]);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let mut groups: Vec<&str> = stdout.split("\n\n").map(|group| group.trim_end()).collect();
+        groups.sort();
+        assert_eq!(groups, ["./a.txt\n1:foo\n3:foo foo", "./c.txt\n1:foo"]);
+    }
+    let output = run(dir.path(), &["--no-heading", "foo", "c.txt"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "c.txt:1:foo\n");
+}
+
+#[test]
+fn hidden_files_are_skipped_by_default() {
+    let dir = tree(&[(".dot", "foo\n"), (".hid/x", "foo\n"), ("a.txt", "foo\n")]);
+    assert_eq!(sorted_stdout(dir.path(), &["-l", "foo", "."]).0, ["./a.txt"]);
+    assert_eq!(
+        sorted_stdout(dir.path(), &["-l", "--hidden", "foo", "."]).0,
+        ["./.dot", "./.hid/x", "./a.txt"]
+    );
+    // 明确给出的隐藏文件总是被搜索
+    assert_eq!(sorted_stdout(dir.path(), &["-l", "foo", ".dot"]).0, [".dot"]);
+}
+
+#[test]
+fn pattern_starting_with_at() {
+    let dir = tree(&[("A.java", "@Override\nvoid run() {}\n")]);
+    for args in [&["-e", "@Override", "A.java"][..], &["-F", "--", "@Override", "A.java"]] {
+        assert_eq!(sorted_stdout(dir.path(), args), (vec!["A.java:1:@Override".to_string()], Some(0)));
+    }
+}
+
+#[test]
+fn arg_file_is_expanded() {
+    let dir = tree(&[("args", "-w\nrun\n"), ("A.java", "@Override\nvoid run() {}\n")]);
+    assert_eq!(
+        sorted_stdout(dir.path(), &["@args", "A.java"]),
+        (vec!["A.java:2:void run() {}".to_string()], Some(0))
+    );
+}
+
+#[test]
+fn replace_with_capture_groups() {
+    let dir = tree(&[("kv", "key=val\nfoo bar\n")]);
+    assert_eq!(
+        sorted_stdout(dir.path(), &["(?P<k>\\w+)=", "-r", "${k}:", "kv"]),
+        (vec!["kv:1:key:val".to_string()], Some(0))
+    );
+    // 多个 -e 时每个匹配使用产生它的模式的捕获组
+    assert_eq!(
+        sorted_stdout(dir.path(), &["-e", "(\\w+)=", "-e", "f(o+)", "-r", "<$1>", "kv"]),
+        (vec!["kv:1:<key>val".to_string(), "kv:2:<oo> bar".to_string()], Some(0))
+    );
+}
+
+#[test]
+fn multiline_line_regexp() {
+    let dir = tree(&[("ml", "bar\nfoo\nfoox\n")]);
+    for args in [&["-U", "-x", "foo", "ml"][..], &["-U", "-F", "-x", "foo", "ml"]] {
+        assert_eq!(sorted_stdout(dir.path(), args), (vec!["ml:2:foo".to_string()], Some(0)));
+    }
+    assert_eq!(
+        sorted_stdout(dir.path(), &["-U", "o\\nfoo", "ml"]),
+        (vec!["ml:2:foo".to_string(), "ml:3:foox".to_string()], Some(0))
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn follow_skips_symlink_cycles_within_max_depth() {
+    let dir = tree(&[("a/f.txt", "hit\n")]);
+    std::os::unix::fs::symlink("..", dir.path().join("a/up")).unwrap();
+    for jobs in ["-j1", "-j4"] {
+        let output = run(dir.path(), &[jobs, "-L", "-d", "5", "hit", "."]);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "./a/f.txt:1:hit\n");
+        assert!(output.stderr.is_empty());
+        assert_eq!(output.status.code(), Some(0));
+        // a/f.txt 在第 2 层
+        assert_eq!(run(dir.path(), &[jobs, "-L", "-d", "1", "hit", "."]).status.code(), Some(1));
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn follow_reports_dangling_symlinks_and_keeps_walking() {
+    let dir = tree(&[("a/f.txt", "hit\n"), ("b/g.txt", "hit\n")]);
+    std::os::unix::fs::symlink("missing", dir.path().join("a/dangling")).unwrap();
+    // 单线程遍历、并行遍历和排序遍历
+    for args in [&["-j1"][..], &["-j4"], &["--sort", "path"]] {
+        let args = [args, &["-L", "hit", "."]].concat();
+        let output = run(dir.path(), &args);
+        let mut lines: Vec<&str> = std::str::from_utf8(&output.stdout).unwrap().lines().collect();
+        lines.sort();
+        assert_eq!(lines, ["./a/f.txt:1:hit", "./b/g.txt:1:hit"]);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("a/dangling"));
+        assert_eq!(output.status.code(), Some(2));
+    }
+    // 不跟随链接时悬空的链接只是一个链接条目，不是错误
+    assert_eq!(run(dir.path(), &["-j1", "hit", "."]).status.code(), Some(0));
+}
+
+#[test]
+fn dedup_lines_across_files() {
+    let dir = tree(&[("a.txt", "use foo;\nfoo()\n"), ("b.txt", "use foo;\n")]);
+    let output = run(dir.path(), &["--dedup-lines", "--sort", "path", "foo", "."]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "./a.txt:1:use foo;\n./a.txt:2:foo()\n"
+    );
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn root_gitignore_applies_within_max_depth() {
+    let dir = tree(&[
+        (".gitignore", "*.log\n"),
+        ("a.log", "hit\n"),
+        ("a.txt", "hit\n"),
+        ("sub/b.log", "hit\n"),
+        ("sub/b.txt", "hit\n"),
+        ("sub/deep/c.txt", "hit\n"),
+    ]);
+    assert_eq!(
+        sorted_stdout(dir.path(), &["-d", "1", "hit", "."]),
+        (vec!["./a.txt:1:hit".to_string()], Some(0))
+    );
+    assert_eq!(
+        sorted_stdout(dir.path(), &["-d", "2", "hit", "."]),
+        (vec!["./a.txt:1:hit".to_string(), "./sub/b.txt:1:hit".to_string()], Some(0))
+    );
+}
+
+#[test]
+fn glob_and_type_filters_apply_before_counting() {
+    let dir = tree(&[("a.rs", "hit hit\n"), ("b.txt", "hit\n"), ("c.md", "hit\n")]);
+    assert_eq!(
+        sorted_stdout(dir.path(), &["-g", "*.rs", "-c", "hit", "."]),
+        (vec!["./a.rs:1".to_string()], Some(0))
+    );
+    assert_eq!(
+        sorted_stdout(dir.path(), &["-g", "!*.txt", "--count-matches", "hit", "."]),
+        (vec!["./a.rs:2".to_string(), "./c.md:1".to_string()], Some(0))
+    );
+    assert_eq!(
+        sorted_stdout(dir.path(), &["-T", "rust", "-c", "--total", "hit", "."]),
+        (vec!["2".to_string()], Some(0))
+    );
+
+    let output = run(dir.path(), &["-t", "rust", "--stats", "hit", "."]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for line in ["2 matches", "1 files contained matches", "1 files searched", "8 bytes searched"] {
+        assert!(stdout.lines().any(|l| l == line), "{:?} not in {}", line, stdout);
+    }
+}
+
+#[test]
+fn max_count_applies_to_each_file() {
+    let dir = tree(&[("a.txt", "hit 1\nhit 2\nhit 3\n"), ("b.txt", "hit 1\nhit 2\n"), ("c.txt", "hit 1\n")]);
+    for jobs in ["-j1", "-j4"] {
+        assert_eq!(
+            sorted_stdout(dir.path(), &[jobs, "-m", "2", "hit", "."]),
+            (
+                vec![
+                    "./a.txt:1:hit 1".to_string(),
+                    "./a.txt:2:hit 2".to_string(),
+                    "./b.txt:1:hit 1".to_string(),
+                    "./b.txt:2:hit 2".to_string(),
+                    "./c.txt:1:hit 1".to_string(),
+                ],
+                Some(0)
+            )
+        );
+        assert_eq!(
+            sorted_stdout(dir.path(), &[jobs, "-m", "1", "-c", "hit", "."]),
+            (vec!["./a.txt:1".to_string(), "./b.txt:1".to_string(), "./c.txt:1".to_string()], Some(0))
+        );
+    }
+}
+
+#[test]
+fn sort_path_is_component_wise() {
+    let dir = tree(&[("a.txt", "hit\n"), ("a-b.txt", "hit\n"), ("a/z.txt", "hit\n"), ("B.txt", "hit\n")]);
+    let output = run(dir.path(), &["--sort", "path", "-l", "hit", "."]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "./B.txt\n./a/z.txt\n./a-b.txt\n./a.txt\n");
+}
+
+#[test]
+fn unrestricted_levels() {
+    let dir = tree(&[
+        (".gitignore", "ignored.txt\n"),
+        ("ignored.txt", "hit\n"),
+        (".hidden.txt", "hit\n"),
+        ("plain.txt", "hit\n"),
+    ]);
+    fs::write(dir.path().join("binary.dat"), b"hit\0\0 binary\n").unwrap();
+    let found = |args: &[&str]| sorted_stdout(dir.path(), &[args, &["-l", "hit", "."]].concat()).0;
+    assert_eq!(found(&[]), ["./plain.txt"]);
+    assert_eq!(found(&["-u"]), ["./ignored.txt", "./plain.txt"]);
+    assert_eq!(found(&["-uu"]), ["./.hidden.txt", "./ignored.txt", "./plain.txt"]);
+    assert_eq!(found(&["-uuu"]), ["./.hidden.txt", "./binary.dat", "./ignored.txt", "./plain.txt"]);
+}
+
+#[test]
+fn backtracking_engine_supports_look_around_and_backreferences() {
+    let dir = tree(&[("p", "cost $42\nit is is here\nfoo\n")]);
+    assert_eq!(
+        sorted_stdout(dir.path(), &["-P", "-o", "(?<=\\$)\\d+", "p"]),
+        (vec!["p:1:42".to_string()], Some(0))
+    );
+    assert_eq!(
+        sorted_stdout(dir.path(), &["-P", "\\b(\\w+) \\1\\b", "-r", "<$1>", "p"]),
+        (vec!["p:2:it <is> here".to_string()], Some(0))
+    );
+    // 默认引擎不支持环视
+    assert_eq!(run(dir.path(), &["(?<=\\$)\\d+", "p"]).status.code(), Some(2));
+}
+
+#[test]
+fn json_event_sequence() {
+    let dir = tree(&[("a.txt", "foo\nbar\n"), ("b.txt", "bar\n"), ("c.txt", "foo foo\n")]);
+    let output = run(dir.path(), &["--json", "--sort", "path", "foo", "."]);
+    let types: Vec<String> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| line.split('"').nth(3).unwrap().to_string())
+        .collect();
+    assert_eq!(types, ["begin", "match", "end", "begin", "match", "end", "summary"]);
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn replace_count_reports_matching_lines() {
+    let dir = tree(&[("a.txt", "foo\nbar\nfoo foo\n"), ("b.txt", "foo\n"), ("c.txt", "baz\n")]);
+    let output = run(dir.path(), &["-c", "-r", "X", "foo", "."]);
+    let mut lines: Vec<String> = String::from_utf8(output.stdout).unwrap().lines().map(String::from).collect();
+    // 汇总在所有文件的计数之后
+    assert_eq!(lines.pop().unwrap(), "3 lines in 2 files would be changed");
+    lines.sort();
+    assert_eq!(lines, ["./a.txt:2", "./b.txt:1"]);
+    // 替换结果与原文相同的行同样计入
+    let output = run(dir.path(), &["-c", "-r", "foo", "foo", "b.txt"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "b.txt:1\n1 lines in 1 files would be changed\n");
+}
+
+#[test]
+fn multiline_match_spans_lines() {
+    let dir = tree(&[("a.txt", "x foo\nbar y\nzzz\nfoo\nbar\n")]);
+    // 跨行的匹配覆盖的每一行都输出，行号对应原文件
+    let output = run(dir.path(), &["-U", "foo\\nbar", "a.txt"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "a.txt:1:x foo\na.txt:2:bar y\na.txt:4:foo\na.txt:5:bar\n"
+    );
+    assert_eq!(sorted_stdout(dir.path(), &["-U", "-c", "foo\\nbar", "a.txt"]).0, ["a.txt:4"]);
+    // 不加 -U 时模式不能跨行
+    assert_eq!(sorted_stdout(dir.path(), &["foo\\nbar", "a.txt"]), (vec![], Some(1)));
+}
+
+#[test]
+fn multiline_replace() {
+    let dir = tree(&[("a.txt", "x foo\nbar y\nzzz\nfoo\nbar\n")]);
+    // 替换作用在整个跨行的匹配上，结果输出在匹配开始的那一行
+    let output = run(dir.path(), &["-U", "-r", "X", "foo\\nbar", "a.txt"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "a.txt:1:x X y\na.txt:4:X\n");
+    assert_eq!(output.status.code(), Some(0));
+    let output = run(dir.path(), &["-U", "-r", "[$0]", "o\\nb", "a.txt"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "a.txt:1:x fo[o\nb]ar y\na.txt:4:fo[o\nb]ar\n");
+    // 计数仍然是匹配覆盖的行数
+    let output = run(dir.path(), &["-U", "-c", "-r", "X", "foo\\nbar", "a.txt"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "a.txt:4\n4 lines in 1 files would be changed\n");
+}
+
+#[test]
+fn multiline_replace_with_capture_groups() {
+    let dir = tree(&[("a.txt", "key =\n  value\nother\n")]);
+    let output = run(dir.path(), &["-U", "-r", "$1: $2", "(\\w+) =\\n\\s*(\\w+)", "a.txt"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "a.txt:1:key: value\n");
+    let output = run(dir.path(), &["-U", "-r", "${v}", "=\\n\\s*(?P<v>\\w+)", "a.txt"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "a.txt:1:key value\n");
+}
+
+#[test]
+fn file_inside_directory_argument_is_searched_once() {
+    let dir = tree(&[("src/main.rs", "fn main() {}\n"), ("src/lib.rs", "fn lib() {}\n")]);
+    for jobs in ["-j1", "-j4"] {
+        assert_eq!(
+            sorted_stdout(dir.path(), &[jobs, "fn", "src/main.rs", "src"]),
+            (vec!["src/lib.rs:1:fn lib() {}".to_string(), "src/main.rs:1:fn main() {}".to_string()], Some(0))
+        );
+    }
+}
+
+#[test]
+fn explicit_files_bypass_ignore_rules() {
+    let dir = tree(&[(".gitignore", "*.log\n"), ("a.log", "hit\n"), ("b.txt", "hit\n")]);
+    fs::create_dir(dir.path().join(".git")).unwrap();
+    // 遍历目录时被忽略，明确给出时照常搜索
+    assert_eq!(sorted_stdout(dir.path(), &["-l", "hit", "."]).0, ["./b.txt"]);
+    assert_eq!(sorted_stdout(dir.path(), &["hit", "a.log"]), (vec!["a.log:1:hit".to_string()], Some(0)));
+}
+
+#[test]
+fn no_pcre2_unicode_matches_ascii_classes() {
+    let dir = tree(&[("a.txt", "café\nnaïve\ncafe\n")]);
+    let output = run(dir.path(), &["-P", "-o", "\\w+", "a.txt"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "a.txt:1:café\na.txt:2:naïve\na.txt:3:cafe\n");
+    let output = run(dir.path(), &["-P", "--no-pcre2-unicode", "-o", "\\w+", "a.txt"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "a.txt:1:caf\na.txt:2:na\na.txt:2:ve\na.txt:3:cafe\n"
+    );
+    // 只对 -P 有意义
+    assert_eq!(run(dir.path(), &["--no-pcre2-unicode", "caf", "a.txt"]).status.code(), Some(2));
+}
+
+#[test]
+fn utf8_and_utf16_files_in_one_search() {
+    let utf16le = |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(u16::to_le_bytes).collect() };
+    let dir = tree(&[("a.txt", "foo\nbar\n")]);
+    let mut bom = b"\xFF\xFE".to_vec();
+    bom.extend(utf16le("bar\nfoo\n"));
+    fs::write(dir.path().join("b.txt"), bom).unwrap();
+    // 交错的 NUL 但不是文本：按二进制文件跳过，而不是当成 UTF-16 搜索
+    fs::write(dir.path().join("c.bin"), utf16le("foo\u{1}\u{2}\u{3}\u{4}\n")).unwrap();
+    assert_eq!(
+        sorted_stdout(dir.path(), &["foo", "."]),
+        (vec!["./a.txt:1:foo".to_string(), "./b.txt:2:foo".to_string()], Some(0))
+    );
+    // 明确指定编码时不再猜测
+    assert_eq!(sorted_stdout(dir.path(), &["-l", "--encoding", "utf-16le", "foo", "c.bin"]).0, ["c.bin"]);
+}
+
+#[test]
+fn stdin_binary_detection_follows_unrestricted_levels() {
+    let dir = tree(&[]);
+    let input = b"hit\nmore\0 hit\n";
+    // -u 和 -uu 只放宽文件过滤，二进制数据仍然跳过；-uuu 按文本搜索
+    for args in [&["hit"][..], &["-u", "hit"], &["-uu", "hit"]] {
+        let output = run_with_input(dir.path(), args, input);
+        assert!(output.stdout.is_empty());
+        assert_eq!(output.status.code(), Some(1));
+    }
+    let output = run_with_input(dir.path(), &["-uuu", "hit"], input);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "<stdin>:1:hit\n<stdin>:2:more\0 hit\n");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn only_matching_in_heading_mode() {
+    let dir = tree(&[("a.txt", "foo x fooo\nbar\nfoo\n")]);
+    let output = run(dir.path(), &["--heading", "-o", "-A1", "fo+", "a.txt"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "a.txt\n1:foo\n  fooo\n2-bar\n3:foo\n");
+}
+
+#[test]
+fn glob_filters_agree_with_gitignore_rules() {
+    let files = [
+        ("a/b", "hit\n"),
+        ("a/x/y/b", "hit\n"),
+        ("a/xb", "hit\n"),
+        ("src/foo", "hit\n"),
+        ("foo/inner.txt", "hit\n"),
+        ("y.txt", "hit\n"),
+        ("x.txt", "hit\n"),
+        ("ab.txt", "hit\n"),
+    ];
+    for pattern in ["a/**/b", "**/foo", "foo/**", "[!x].txt"] {
+        let by_glob = tree(&files);
+        let exclude = format!("!{}", pattern);
+        let (excluded_by_glob, _) = sorted_stdout(by_glob.path(), &["-l", "-g", &exclude, "hit", "."]);
+
+        let by_gitignore = tree(&files);
+        fs::create_dir(by_gitignore.path().join(".git")).unwrap();
+        fs::write(by_gitignore.path().join(".gitignore"), format!("{}\n", pattern)).unwrap();
+        let (ignored, _) = sorted_stdout(by_gitignore.path(), &["-l", "hit", "."]);
+        assert_eq!(excluded_by_glob, ignored, "{}", pattern);
+        assert!(ignored.len() < files.len(), "{}", pattern);
+    }
+}
+
+// JSON 对象中 `"key":` 之后的数字或字符串（测试数据中没有需要转义的字符）
+fn json_field<'a>(json: &'a str, key: &str) -> &'a str {
+    let rest = &json[json.find(&format!("\"{}\":", key)).unwrap() + key.len() + 3..];
+    match rest.strip_prefix('"') {
+        Some(text) => &text[..text.find('"').unwrap()],
+        None => &rest[..rest.find([',', '}']).unwrap()],
+    }
+}
+
+#[test]
+fn json_submatch_offsets() {
+    let content = "héllo wörld foo\nplain\n日本語 foo wörld\n";
+    let dir = tree(&[("a.txt", content)]);
+    let output = run(dir.path(), &["--json", "w.rld|foo", "a.txt"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let events: Vec<&str> = stdout.lines().filter(|line| line.starts_with(r#"{"type":"match""#)).collect();
+    assert_eq!(events.len(), 2);
+
+    let mut submatches = 0;
+    for event in events {
+        let text = json_field(json_field(event, "lines"), "text");
+        let number: usize = json_field(event, "line_number").parse().unwrap();
+        let offset: usize = json_field(event, "absolute_offset").parse().unwrap();
+        assert_eq!(content.lines().nth(number - 1), Some(text));
+        assert_eq!(&content[offset..offset + text.len()], text);
+        // 每个 submatch 的字节范围和字符范围都指向同一段文本
+        for submatch in event.split(r#"{"match":"#).skip(1) {
+            let start: usize = json_field(submatch, "start").parse().unwrap();
+            let end: usize = json_field(submatch, "end").parse().unwrap();
+            let char_start: usize = json_field(submatch, "char_start").parse().unwrap();
+            let char_end: usize = json_field(submatch, "char_end").parse().unwrap();
+            assert_eq!(&text[start..end], json_field(submatch, "text"));
+            assert_eq!(text[..start].chars().count(), char_start);
+            assert_eq!(text[..end].chars().count(), char_end);
+            submatches += 1;
+        }
+    }
+    assert_eq!(submatches, 4);
+}
+
+#[test]
+fn anchor_only_patterns_match_each_line_once() {
+    let dir = tree(&[("a.txt", "foo\n\nbar baz\n")]);
+    assert_eq!(
+        sorted_stdout(dir.path(), &["--vimgrep", "^", "a.txt"]).0,
+        ["a.txt:1:1:foo", "a.txt:2:1:", "a.txt:3:1:bar baz"]
+    );
+    // `$` 的列号指向行尾
+    assert_eq!(
+        sorted_stdout(dir.path(), &["--vimgrep", "$", "a.txt"]).0,
+        ["a.txt:1:4:foo", "a.txt:2:1:", "a.txt:3:8:bar baz"]
+    );
+    assert_eq!(sorted_stdout(dir.path(), &["--count-matches", "^|$", "a.txt"]).0, ["a.txt:3"]);
+}
+
+#[test]
+fn max_count_keeps_the_after_context_of_the_last_match() {
+    let dir = tree(&[("a.txt", "a\nfoo 1\nb\nc\nfoo 2\nd\n"), ("b.txt", "a\nfoo 1\nfoo 2\nd\n")]);
+    let output = run(dir.path(), &["-C", "1", "--max-count", "1", "foo", "a.txt"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a.txt-1-a\na.txt:2:foo 1\na.txt-3-b\n");
+    // 上限之后的匹配行只作为上下文输出
+    let output = run(dir.path(), &["-C", "1", "--max-count", "1", "foo", "b.txt"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "b.txt-1-a\nb.txt:2:foo 1\nb.txt-3-foo 2\n");
+}
+
+#[test]
+fn include_dir_only_descends_into_whitelisted_directories() {
+    let dir = tree(&[
+        ("top.txt", "foo\n"),
+        ("src/a.txt", "foo\n"),
+        ("src/inner/b.txt", "foo\n"),
+        ("src2/c.txt", "foo\n"),
+        ("tests/d.txt", "foo\n"),
+        ("docs/e.txt", "foo\n"),
+    ]);
+    // 根目录本身不会被剪掉，白名单目录的子目录也会被搜索
+    assert_eq!(
+        sorted_stdout(dir.path(), &["--include-dir", "src", "foo", "."]).0,
+        ["./src/a.txt:1:foo", "./src/inner/b.txt:1:foo", "./top.txt:1:foo"]
+    );
+    // 多个模式取并集
+    assert_eq!(
+        sorted_stdout(dir.path(), &["--include-dir", "src*", "--include-dir", "tests", "foo", "."]).0,
+        [
+            "./src/a.txt:1:foo",
+            "./src/inner/b.txt:1:foo",
+            "./src2/c.txt:1:foo",
+            "./tests/d.txt:1:foo",
+            "./top.txt:1:foo",
+        ]
+    );
+}
+
+#[test]
+fn glob_overrides_type_filters() {
+    let dir = tree(&[("a.rs", "hit\n"), ("b.rs", "hit\n"), ("c.md", "hit\n"), ("d.txt", "hit\n")]);
+    let cases: &[(&[&str], &[&str])] = &[
+        // 不属于该类型、但命中包含 glob 的文件被搜索；有包含 glob 时只搜索命中它的文件
+        (&["-t", "rust", "-g", "*.md"], &["./c.md:1:hit"]),
+        (&["-t", "rust", "-g", "b.*"], &["./b.rs:1:hit"]),
+        // 属于该类型、但命中排除 glob 的文件被跳过
+        (&["-t", "rust", "-g", "!b.rs"], &["./a.rs:1:hit"]),
+        // 只有排除 glob 时类型过滤照常生效
+        (&["-t", "rust", "-g", "!c.md"], &["./a.rs:1:hit", "./b.rs:1:hit"]),
+        // 包含 glob 同样覆盖 --type-not
+        (&["-T", "rust", "-g", "a.rs"], &["./a.rs:1:hit"]),
+        (&["-T", "rust", "-g", "!c.md"], &["./d.txt:1:hit"]),
+    ];
+    for (filters, expected) in cases {
+        let args: Vec<&str> = filters.iter().copied().chain(["hit", "."]).collect();
+        assert_eq!(sorted_stdout(dir.path(), &args).0, *expected, "{:?}", filters);
+    }
+}
+
+#[test]
+fn piped_output_is_not_grouped_by_default() {
+    let dir = tree(&[("a.txt", "foo\n"), ("b.txt", "foo\n")]);
+    // 测试中标准输出是管道
+    let output = run(dir.path(), &["-j1", "foo", "a.txt", "b.txt"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a.txt:1:foo\nb.txt:1:foo\n");
+    let output = run(dir.path(), &["-j1", "--heading", "foo", "a.txt", "b.txt"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a.txt\n1:foo\n\nb.txt\n1:foo\n");
+    // 后出现的选项生效
+    let output = run(dir.path(), &["--no-heading", "--heading", "foo", "a.txt"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a.txt\n1:foo\n");
+    let output = run(dir.path(), &["--heading", "--no-heading", "foo", "a.txt"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a.txt:1:foo\n");
+}
+
+#[test]
+fn count_lines_and_count_matches_differ() {
+    let dir = tree(&[("a.txt", "foo foo\nbar\nfoo\n")]);
+    assert_eq!(sorted_stdout(dir.path(), &["--count", "foo", "a.txt"]), (vec!["a.txt:2".to_string()], Some(0)));
+    assert_eq!(
+        sorted_stdout(dir.path(), &["--count-matches", "foo", "a.txt"]),
+        (vec!["a.txt:3".to_string()], Some(0))
+    );
+    // 两者不能同时使用
+    let output = run(dir.path(), &["--count", "--count-matches", "foo", "a.txt"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used with"));
+}
+
+#[test]
+fn overlapping_path_arguments_are_searched_once() {
+    let dir = tree(&[("top.txt", "foo\n"), ("src/a.txt", "foo\n"), ("src/inner/b.txt", "foo\n")]);
+    let all = ["./src/a.txt:1:foo", "./src/inner/b.txt:1:foo", "./top.txt:1:foo"];
+    for jobs in ["-j1", "-j4"] {
+        // 祖先目录在前或在后都一样
+        assert_eq!(sorted_stdout(dir.path(), &[jobs, "foo", ".", "./src"]).0, all);
+        assert_eq!(sorted_stdout(dir.path(), &[jobs, "foo", "./src", "."]).0, all);
+    }
+    // 同一个文件以不同写法给出多次
+    assert_eq!(sorted_stdout(dir.path(), &["foo", "top.txt", "top.txt", "./top.txt"]).0, ["top.txt:1:foo"]);
+}
+
+#[test]
+fn glob_with_trailing_slash_matches_only_directories() {
+    let dir = tree(&[
+        ("build/in.txt", "hit\n"),
+        ("sub/build/in.txt", "hit\n"),
+        ("x/build", "hit\n"),
+        ("top.txt", "hit\n"),
+    ]);
+    // 跳过名为 build 的目录及其中的全部内容，名为 build 的文件不受影响
+    assert_eq!(
+        sorted_stdout(dir.path(), &["-g", "!build/", "hit", "."]).0,
+        ["./top.txt:1:hit", "./x/build:1:hit"]
+    );
+    assert_eq!(
+        sorted_stdout(dir.path(), &["-g", "build/", "hit", "."]).0,
+        ["./build/in.txt:1:hit", "./sub/build/in.txt:1:hit"]
+    );
+}
+
+#[test]
+fn config_file_supplies_default_flags() {
+    let dir = tree(&[
+        ("search/.hidden.txt", "Foo\nfoo\nfoo 3\n"),
+        ("config", "# defaults\n--hidden\n\n-S\n--max-count=1\n"),
+    ]);
+    let run_with_config = |config: &str, args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_core"))
+            .args(args)
+            .current_dir(dir.path().join("search"))
+            .env("GREPDOJO_CONFIG", dir.path().join(config))
+            .stdin(Stdio::null())
+            .output()
+            .unwrap()
+    };
+    let output = run_with_config("config", &["foo", "."]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "./.hidden.txt:1:Foo\n");
+    // 命令行中的参数覆盖配置文件
+    let output = run_with_config("config", &["--max-count", "2", "foo", "."]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "./.hidden.txt:1:Foo\n./.hidden.txt:2:foo\n");
+    let output = run_with_config("missing", &["foo", "."]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Failed to read config file"));
+}
+
+#[test]
+fn fixed_strings_with_several_patterns() {
+    let dir = tree(&[("a.txt", "foobar a.b axb\nnone\nxfoo baz\n")]);
+    let args = ["-F", "-e", "foo", "-e", "a.b", "-e", "baz"];
+    let vimgrep: Vec<&str> = args.iter().copied().chain(["--vimgrep", "a.txt"]).collect();
+    assert_eq!(
+        sorted_stdout(dir.path(), &vimgrep).0,
+        ["a.txt:1:1:foobar a.b axb", "a.txt:1:8:foobar a.b axb", "a.txt:3:2:xfoo baz", "a.txt:3:6:xfoo baz"]
+    );
+    let only_matching: Vec<&str> = args.iter().copied().chain(["-o", "a.txt"]).collect();
+    assert_eq!(
+        sorted_stdout(dir.path(), &only_matching).0,
+        ["a.txt:1:a.b", "a.txt:1:foo", "a.txt:3:baz", "a.txt:3:foo"]
+    );
+}
+
+#[test]
+fn count_from_stdin() {
+    let dir = tree(&[]);
+    let input = b"foo\nbar\nfoo foo\n";
+    let stdout = |args: &[&str], input: &[u8]| String::from_utf8(run_with_input(dir.path(), args, input).stdout).unwrap();
+    assert_eq!(stdout(&["-c", "foo"], input), "<stdin>:2\n");
+    assert_eq!(stdout(&["-c", "--no-filename", "foo"], input), "2\n");
+    assert_eq!(stdout(&["--count-matches", "foo", "-"], input), "<stdin>:3\n");
+    // 没有匹配时与文件一样不输出
+    let output = run_with_input(dir.path(), &["-c", "foo"], b"bar\n");
+    assert_eq!((output.stdout.is_empty(), output.status.code()), (true, Some(1)));
+}
+
+#[test]
+fn closed_output_pipe_exits_quietly() {
+    use std::io::{BufRead, BufReader};
+
+    // 输出远大于管道缓冲区，读取方提前关闭时写入一定会失败
+    let dir = tree(&[("a.txt", &"foo line\n".repeat(200_000))]);
+    let mut child = Command::new(env!("CARGO_BIN_EXE_core"))
+        .args(["foo", "a.txt"])
+        .current_dir(dir.path())
+        .env_remove("GREPDOJO_CONFIG")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut first = String::new();
+    BufReader::new(child.stdout.take().unwrap()).read_line(&mut first).unwrap();
+    assert_eq!(first, "a.txt:1:foo line\n");
+    // 读取端在这里被丢弃
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
+
+#[test]
+fn max_count_is_the_same_in_every_output_mode() {
+    let dir = tree(&[("a.txt", "hit 1\nmiss 2\nhit hit 3\nmiss 4\nhit 5\nmiss 6\n")]);
+    let cases: &[(&[&str], &[&str])] = &[
+        (&[], &["a.txt:1:hit 1", "a.txt:3:hit hit 3"]),
+        (&["-v"], &["a.txt:2:miss 2", "a.txt:4:miss 4"]),
+        (&["-c"], &["a.txt:2"]),
+        (&["-v", "-c"], &["a.txt:2"]),
+        // 上限计的是行：第 3 行的两个匹配都输出
+        (&["-o"], &["a.txt:1:hit", "a.txt:3:hit", "a.txt:3:hit"]),
+        (&["--count-matches"], &["a.txt:3"]),
+    ];
+    for (mode, expected) in cases {
+        let args: Vec<&str> = ["-m", "2"].into_iter().chain(mode.iter().copied()).chain(["hit", "a.txt"]).collect();
+        assert_eq!(sorted_stdout(dir.path(), &args).0, *expected, "{:?}", mode);
+    }
+}
+
+#[test]
+fn type_clear_then_redefine() {
+    let dir = tree(&[("a.rs", "hit\n"), ("b.rsx", "hit\n"), ("c.txt", "hit\n")]);
+    assert_eq!(sorted_stdout(dir.path(), &["-t", "rust", "hit", "."]).0, ["./a.rs:1:hit"]);
+    // 内置的 `*.rs` 被清除，只剩下新加的定义
+    assert_eq!(
+        sorted_stdout(dir.path(), &["--type-clear", "rust", "--type-add", "rust:*.rsx", "-t", "rust", "hit", "."]).0,
+        ["./b.rsx:1:hit"]
+    );
+    assert_eq!(sorted_stdout(dir.path(), &["--type-clear", "rust", "-t", "rust", "hit", "."]), (vec![], Some(1)));
+}
+
+#[test]
+fn anchored_and_unanchored_globs() {
+    let dir = tree(&[("foo", "hit\n"), ("a/foo", "hit\n"), ("a/b/foo", "hit\n"), ("a/bar", "hit\n")]);
+    let cases: &[(&str, &[&str])] = &[
+        // 不含 `/` 的 glob 匹配任意深度的文件名
+        ("foo", &["./a/b/foo:1:hit", "./a/foo:1:hit", "./foo:1:hit"]),
+        ("!foo", &["./a/bar:1:hit"]),
+        // 开头的 `/` 锚定到搜索根目录
+        ("/foo", &["./foo:1:hit"]),
+        ("!/foo", &["./a/b/foo:1:hit", "./a/bar:1:hit", "./a/foo:1:hit"]),
+        // 与 gitignore 一样，含 `/` 的 glob 同样从根目录开始匹配
+        ("a/foo", &["./a/foo:1:hit"]),
+        ("b/foo", &[]),
+        ("/a/*", &["./a/bar:1:hit", "./a/foo:1:hit"]),
+    ];
+    for (glob, expected) in cases {
+        assert_eq!(sorted_stdout(dir.path(), &["-g", glob, "hit", "."]).0, *expected, "{}", glob);
+    }
+}
+
+#[test]
+fn null_data_with_only_matching() {
+    let dir = tree(&[("a.bin", "foo one\0bar\0x\nfoo foo\0")]);
+    // 记录序号作为行号，每个匹配之后是 NUL；记录中的换行不分隔记录
+    let output = run(dir.path(), &["--null-data", "-o", "foo", "a.bin"]);
+    assert_eq!(output.stdout, b"a.bin:1:foo\0a.bin:3:foo\0a.bin:3:foo\0");
+    let output = run(dir.path(), &["--null-data", "-o", "-r", "[$0]", "x\nfoo", "a.bin"]);
+    assert_eq!(output.stdout, b"a.bin:3:[x\nfoo]\0");
+}
+
+#[test]
+fn glob_brace_expansion() {
+    let dir = tree(&[("a.rs", "hit\n"), ("b.md", "hit\n"), ("c.txt", "hit\n"), ("d/e.rs", "hit\n")]);
+    assert_eq!(
+        sorted_stdout(dir.path(), &["-g", "*.{rs,md}", "hit", "."]).0,
+        ["./a.rs:1:hit", "./b.md:1:hit", "./d/e.rs:1:hit"]
+    );
+    assert_eq!(sorted_stdout(dir.path(), &["-g", "!*.{rs,md}", "hit", "."]).0, ["./c.txt:1:hit"]);
+}
+
+#[test]
+fn invert_match_with_context() {
+    let dir = tree(&[("a.txt", "foo 1\nfoo 2\nbar 3\nfoo 4\nfoo 5\nfoo 6\nbaz 7\nfoo 8\n")]);
+    // 不匹配的行是命中的行，周围匹配模式的行作为上下文
+    let output = run(dir.path(), &["-v", "-C", "1", "foo", "a.txt"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "a.txt-2-foo 2\na.txt:3:bar 3\na.txt-4-foo 4\n--\na.txt-6-foo 6\na.txt:7:baz 7\na.txt-8-foo 8\n"
+    );
+    // 上下文相接时不输出 `--`，相邻的命中行之间不重复输出上下文
+    let output = run(dir.path(), &["-v", "-C", "1", "foo [1-5]", "a.txt"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "a.txt-2-foo 2\na.txt:3:bar 3\na.txt-4-foo 4\na.txt-5-foo 5\na.txt:6:foo 6\na.txt:7:baz 7\na.txt:8:foo 8\n"
+    );
+}
+
+#[test]
+fn missing_paths_do_not_stop_the_search() {
+    let dir = tree(&[("a.txt", "hit\n"), ("b.txt", "hit\n")]);
+    for jobs in ["-j1", "-j4"] {
+        let output = run(dir.path(), &[jobs, "hit", "a.txt", "missing", "b.txt"]);
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "a.txt:1:hit\nb.txt:1:hit\n");
+        assert!(String::from_utf8_lossy(&output.stderr).contains("missing"));
+        assert_eq!(output.status.code(), Some(2));
+    }
+    let output = run(dir.path(), &["hit", "missing", "also-missing"]);
+    assert_eq!((output.stdout.is_empty(), output.status.code()), (true, Some(2)));
+}
+
+#[test]
+fn hex_dump_of_a_binary_match() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("a.bin"), b"\x00\x01ELF\xde\xad\xbe\xef tail\x00").unwrap();
+    // 二进制文件默认不搜索
+    assert_eq!(sorted_stdout(dir.path(), &["ELF", "a.bin"]), (vec![], Some(1)));
+    let output = run(dir.path(), &["--hex", r"(?-u)\xde\xad\xbe\xef", "a.bin"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("a.bin:00000005: {:<47}  ....\n", "de ad be ef")
+    );
+}
+
+#[test]
+fn incompatible_output_flags_are_rejected() {
+    let dir = tree(&[("a.txt", "hit\n")]);
+    let cases: &[(&[&str], &str)] = &[
+        (&["--json", "--field-match-separator", "@"], "--field-match-separator cannot be used with --json"),
+        (&["--json", "--heading"], "--heading cannot be used with --json"),
+        (&["--hex", "--only-matching"], "--only-matching cannot be used with --hex"),
+        (&["--hex", "--field-context-separator", "@"], "--field-context-separator cannot be used with --hex"),
+        (&["--total"], "--total requires --count or --count-matches"),
+    ];
+    for (flags, message) in cases {
+        let args: Vec<&str> = flags.iter().copied().chain(["hit", "a.txt"]).collect();
+        let output = run(dir.path(), &args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", flags);
+        assert!(output.stdout.is_empty(), "{:?}", flags);
+        assert!(String::from_utf8_lossy(&output.stderr).contains(message), "{:?}", flags);
+    }
+}
+
+// Windows 的文件名不能包含换行和 `:`
+#[cfg(unix)]
+#[test]
+fn quote_paths_with_newlines_and_colons() {
+    let dir = tree(&[("a\nb.txt", "hit\n"), ("c:d.txt", "hit\n"), ("plain.txt", "hit\n")]);
+    assert_eq!(
+        sorted_stdout(dir.path(), &["--quote", "hit", "."]).0,
+        ["$'./a\\nb.txt':1:hit", "'./c:d.txt':1:hit", "./plain.txt:1:hit"]
+    );
+    // 不加引号时用 --null 分隔路径
+    let output = run(dir.path(), &["-l", "--null", "hit", "a\nb.txt"]);
+    assert_eq!(output.stdout, b"a\nb.txt\0");
+}
+
+#[test]
+fn max_read_bytes_limits_each_file() {
+    let body = format!("#!/bin/sh needle\n{}needle late\n", "filler line xx\n".repeat(20_000));
+    // 大文件使用 mmap，小文件按块读取，两种方式都只搜索前 100 个字节
+    let small_body = &body[..1000];
+    let dir = tree(&[("big.txt", &body), ("small.txt", small_body)]);
+    for file in ["big.txt", "small.txt"] {
+        assert_eq!(
+            sorted_stdout(dir.path(), &["--max-read-bytes", "100", "needle", file]).0,
+            [format!("{}:1:#!/bin/sh needle", file)]
+        );
+        // 17 + 5 * 15 = 92 字节之后是被截断的第 7 行
+        assert_eq!(
+            sorted_stdout(dir.path(), &["--max-read-bytes", "100", "-c", "filler", file]).0,
+            [format!("{}:6", file)]
+        );
+    }
+    assert_eq!(sorted_stdout(dir.path(), &["-c", "needle", "big.txt"]).0, ["big.txt:2"]);
+}
+
+// 预处理脚本：原样输出文件，同时把每次运行记录到 `log` 中
+#[cfg(unix)]
+fn counting_preprocessor(dir: &Path) -> (std::path::PathBuf, std::path::PathBuf) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let script = dir.join("pre.sh");
+    let log = dir.join("pre.log");
+    fs::write(&script, format!("#!/bin/sh\necho \"$1\" >> '{}'\ncat \"$1\"\n", log.display())).unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+    (script, log)
+}
+
+#[cfg(unix)]
+#[test]
+fn quiet_stops_scanning_after_the_first_match() {
+    let files: Vec<(String, &str)> = (0..200).map(|i| (format!("search/{:03}.txt", i), "hit\n")).collect();
+    let files: Vec<(&str, &str)> = files.iter().map(|(path, content)| (path.as_str(), *content)).collect();
+    let dir = tree(&files);
+    let (script, log) = counting_preprocessor(dir.path());
+    let search = dir.path().join("search");
+    for (jobs, max_scanned) in [("-j1", 1), ("-j4", 20)] {
+        let _ = fs::remove_file(&log);
+        let output = run(&search, &[jobs, "-q", "--pre", script.to_str().unwrap(), "hit", "."]);
+        assert_eq!((output.stdout.is_empty(), output.status.code()), (true, Some(0)));
+        // 已经开始的文件会搜索完，之后的文件都被跳过
+        let scanned = fs::read_to_string(&log).unwrap().lines().count();
+        assert!((1..=max_scanned).contains(&scanned), "{}: {} files scanned", jobs, scanned);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn pre_cache_reuses_output_of_unchanged_files() {
+    let dir = tree(&[("search/a.txt", "hit\n")]);
+    let (script, log) = counting_preprocessor(dir.path());
+    let cache = dir.path().join("cache");
+    let search = dir.path().join("search");
+    let script = script.to_str().unwrap();
+    let args = ["--pre", script, "--pre-cache-dir", cache.to_str().unwrap(), "hit", "a.txt"];
+    let runs = || fs::read_to_string(&log).unwrap().lines().count();
+    for _ in 0..2 {
+        let output = run(&search, &args);
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "a.txt:1:hit\n");
+    }
+    assert_eq!(runs(), 1);
+    // 内容（大小）变化后重新运行
+    fs::write(search.join("a.txt"), "hit again\n").unwrap();
+    let output = run(&search, &args);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a.txt:1:hit again\n");
+    assert_eq!(runs(), 2);
+    // 不指定缓存目录时每次都运行
+    run(&search, &["--pre", script, "hit", "a.txt"]);
+    assert_eq!(runs(), 3);
+}
+
+#[test]
+fn patterns_matching_the_empty_string() {
+    let dir = tree(&[("a.txt", "abc\n\nxyz\n")]);
+    for pattern in ["a|", "(?:)", ""] {
+        // 每一行只报告一次，空匹配的列号是 1
+        assert_eq!(
+            sorted_stdout(dir.path(), &["--vimgrep", pattern, "a.txt"]).0,
+            ["a.txt:1:1:abc", "a.txt:2:1:", "a.txt:3:1:xyz"],
+            "{:?}",
+            pattern
+        );
+        assert_eq!(sorted_stdout(dir.path(), &["--count-matches", pattern, "a.txt"]).0, ["a.txt:3"], "{:?}", pattern);
+    }
+}
+
+#[test]
+fn line_numbers_are_aligned_per_file() {
+    let lines: Vec<&str> = (1..130).map(|i| if [3, 12, 123].contains(&i) { "hit" } else { "x" }).collect();
+    let dir = tree(&[("a.txt", &(lines.join("\n") + "\n")), ("b.txt", "hit\n")]);
+    let output = run(dir.path(), &["-j1", "--heading", "--line-number-width", "auto", "hit", "a.txt", "b.txt"]);
+    // 宽度按每个文件中最大的行号计算
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a.txt\n  3:hit\n 12:hit\n123:hit\n\nb.txt\n1:hit\n");
+    let context = ["--heading", "--line-number-width", "auto", "--field-context-separator", "~", "-C1"];
+    let output = run(dir.path(), &[&context[..], &["hit", "a.txt"]].concat());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("a.txt\n  2~x\n  3:hit\n  4~x\n--\n 11~x\n"), "{}", stdout);
+}
+
+#[test]
+fn quiet_distinguishes_no_match_from_errors() {
+    let dir = tree(&[("a.txt", "hit\n")]);
+    let quiet = |args: &[&str]| {
+        let output = run(dir.path(), &[&["-q"], args].concat());
+        assert!(output.stdout.is_empty(), "{:?}", args);
+        output
+    };
+    let found = quiet(&["hit", "."]);
+    assert_eq!((found.status.code(), found.stderr.is_empty()), (Some(0), true));
+    assert_eq!(quiet(&["nomatch", "."]).status.code(), Some(1));
+    // 没有匹配时出错的路径使退出码为 2
+    let missing = quiet(&["nomatch", "missing", "."]);
+    assert_eq!(missing.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("missing"));
+    assert_eq!(quiet(&["a(", "."]).status.code(), Some(2));
+    // 标准输入同样适用
+    assert_eq!(run_with_input(dir.path(), &["-q", "hit"], b"hit\n").status.code(), Some(0));
+    assert_eq!(run_with_input(dir.path(), &["-q", "hit"], b"miss\n").status.code(), Some(1));
+}
+
+#[test]
+fn total_is_the_sum_of_per_file_counts() {
+    let dir = tree(&[
+        ("a.txt", "hit hit\nhit\n"),
+        ("d/b.txt", "hit\n"),
+        ("d/e/c.txt", "hit x hit\n"),
+        ("none.txt", "x\n"),
+    ]);
+    for (count, expected) in [("-c", 4), ("--count-matches", 6)] {
+        let (lines, _) = sorted_stdout(dir.path(), &[count, "hit", "."]);
+        let sum: usize = lines.iter().map(|line| line.rsplit(':').next().unwrap().parse::<usize>().unwrap()).sum();
+        assert_eq!(sum, expected);
+        // 只输出一行总数
+        for jobs in ["-j1", "-j4"] {
+            let total = sorted_stdout(dir.path(), &[jobs, count, "--total", "hit", "."]);
+            assert_eq!(total, (vec![sum.to_string()], Some(0)));
+        }
+    }
+    // 没有匹配时总数为 0
+    assert_eq!(sorted_stdout(dir.path(), &["-c", "--total", "nomatch", "."]), (vec!["0".to_string()], Some(1)));
+}
@@ -0,0 +1,389 @@
+//! glob 匹配：.gitignore 规则和命令行的路径过滤参数（`-g`、`--type` 等）共用
+//!
+//! 支持 `*`（任意多个非 `/` 字符）、`?`（单个非 `/` 字符）、字符类 `[abc]` / `[a-z]` / `[!a]`
+//! 以及花括号展开 `{a,b}`。独立成段的 `**` 可以跨越目录（与 gitignore 相同）：
+//! `**/foo` 匹配任意深度的 `foo`，`foo/**` 匹配 `foo` 中的所有内容，`a/**/b` 匹配
+//! `a/b`、`a/x/b`、`a/x/y/b`；其他位置的 `**` 与 `*` 相同。`\` 转义下一个字符，使其按字面量匹配（`a\*b` 只匹配名为 `a*b` 的文件）；
+//! 模式末尾单独的 `\` 按字面量处理。
+//!
+//! glob 最终编译为一个锚定的正则，匹配时间与路径长度成线性关系，不会因为
+//! `*a*a*a*b` 这样的模式回溯到指数时间。
+
+use regex::Regex;
+
+/// 预先编译好的 glob：花括号在构造时展开，每个分支解析为一串 token，
+/// 所有分支再合并编译为一个正则，匹配时不再重复解析模式
+#[derive(Debug, Clone)]
+pub struct GlobMatcher {
+    glob: String,
+    regex: Regex,
+}
+
+#[derive(Debug, Clone)]
+enum Token {
+    Literal(char),
+    // `?`
+    AnyChar,
+    // `*`
+    AnyRun,
+    // 开头或 `/` 之后的 `**/`：零个或多个完整的目录（空串，或以 `/` 结尾的任意文本）
+    AnyDirs,
+    // `/` 之后结尾的 `**`（或整个模式就是 `**`）：任意文本，可以包含 `/`
+    AnyPath,
+    // `[...]`，每一项是闭区间
+    Class { ranges: Vec<(char, char)>, negated: bool },
+}
+
+impl GlobMatcher {
+    /// 编译 glob；无法解析的部分（没有闭合的 `[` 或 `{`）按字面量处理，因此不会失败
+    pub fn new(glob: &str) -> Self {
+        let alternatives: Vec<String> = expand_braces(glob)
+            .iter()
+            .map(|alternative| to_regex(&tokenize(alternative)))
+            .collect();
+        // `**` 可以匹配路径中的任意字符（包括换行）
+        let pattern = format!("(?s)^(?:{})$", alternatives.join("|"));
+        GlobMatcher {
+            glob: glob.to_string(),
+            regex: Regex::new(&pattern).expect("每个 token 都转义为合法的正则"),
+        }
+    }
+
+    /// 原始的 glob 文本
+    pub fn glob(&self) -> &str {
+        &self.glob
+    }
+
+    /// 判断 `text` 是否完整匹配
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.is_match(text)
+    }
+}
+
+/// 展开花括号：`*.{rs,toml}` 展开为 `*.rs` 和 `*.toml`
+///
+/// 支持嵌套（`{a,b{c,d}}`）和空分支（`foo{,.bak}`）。
+/// 没有闭合的 `{` 以及空的 `{}` 按字面量处理。
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some((open, close)) = find_braces(pattern) else {
+        return vec![pattern.to_string()];
+    };
+    let prefix = &pattern[..open];
+    let body = &pattern[open + 1..close];
+    let suffix = &pattern[close + 1..];
+
+    // 分支本身和后缀中可能还有花括号，递归展开
+    let suffixes = expand_braces(suffix);
+    let mut expanded = Vec::new();
+    for alternative in split_alternatives(body) {
+        for alternative in expand_braces(alternative) {
+            for suffix in &suffixes {
+                expanded.push(format!("{}{}{}", prefix, alternative, suffix));
+            }
+        }
+    }
+    expanded
+}
+
+// 找到第一对需要展开的花括号（字节位置）；`{}` 以及转义的花括号跳过
+fn find_braces(pattern: &str) -> Option<(usize, usize)> {
+    let mut search_from = 0;
+    while let Some(open) = unescaped(&pattern[search_from..])
+        .find(|&(_, c)| c == '{')
+        .map(|(i, _)| search_from + i)
+    {
+        let mut depth = 0;
+        let close = unescaped(&pattern[open..]).find_map(|(i, c)| {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(open + i);
+                    }
+                }
+                _ => {}
+            }
+            None
+        });
+        match close {
+            Some(close) if close > open + 1 => return Some((open, close)),
+            Some(close) => search_from = close + 1,
+            // 没有闭合：之后也不会再有可展开的花括号
+            None => return None,
+        }
+    }
+    None
+}
+
+// 跳过被 `\` 转义的字符（以及 `\` 本身），只产生有特殊含义的位置
+fn unescaped(text: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut escaped = false;
+    text.char_indices().filter(move |&(_, c)| {
+        let special = !escaped && c != '\\';
+        escaped = !escaped && c == '\\';
+        special
+    })
+}
+
+// 按最外层的 `,` 拆分花括号中的内容
+fn split_alternatives(body: &str) -> Vec<&str> {
+    let mut alternatives = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in unescaped(body) {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                alternatives.push(&body[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    alternatives.push(&body[start..]);
+    alternatives
+}
+
+// 把一个（已经展开过花括号的）分支解析为 token
+fn tokenize(pattern: &str) -> Vec<Token> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let component_start = i == 0 || chars[i - 1] == '/';
+        match chars[i] {
+            '*' if component_start && chars.get(i + 1) == Some(&'*') && chars.get(i + 2) == Some(&'/') => {
+                tokens.push(Token::AnyDirs);
+                i += 3;
+            }
+            '*' if component_start && chars.get(i + 1) == Some(&'*') && i + 2 == chars.len() => {
+                tokens.push(Token::AnyPath);
+                i += 2;
+            }
+            '*' => {
+                // 连续的 `*` 与单个 `*` 等价
+                if !matches!(tokens.last(), Some(Token::AnyRun)) {
+                    tokens.push(Token::AnyRun);
+                }
+                i += 1;
+            }
+            '?' => {
+                tokens.push(Token::AnyChar);
+                i += 1;
+            }
+            '\\' if i + 1 < chars.len() => {
+                tokens.push(Token::Literal(chars[i + 1]));
+                i += 2;
+            }
+            '[' => match parse_class(&chars[i + 1..]) {
+                Some((class, consumed)) => {
+                    tokens.push(class);
+                    i += 1 + consumed;
+                }
+                // 没有闭合的 `[` 按字面量处理
+                None => {
+                    tokens.push(Token::Literal('['));
+                    i += 1;
+                }
+            },
+            c => {
+                tokens.push(Token::Literal(c));
+                i += 1;
+            }
+        }
+    }
+    tokens
+}
+
+// 解析 `[` 之后的字符类，返回 token 以及消耗的字符数（包括 `]`）
+fn parse_class(pattern: &[char]) -> Option<(Token, usize)> {
+    let (negated, body_start) = match pattern.first() {
+        Some('!') | Some('^') => (true, 1),
+        _ => (false, 0),
+    };
+    // `]` 紧跟在 `[` 或 `[!` 之后时属于字符类本身；`\]` 同样属于字符类
+    let mut close = None;
+    let mut i = body_start;
+    while i < pattern.len() {
+        match pattern[i] {
+            '\\' => i += 1,
+            ']' if i > body_start => {
+                close = Some(i);
+                break;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    let close = close?;
+
+    // 先去掉转义，记录每个字符是否被转义：转义的 `-` 不表示区间
+    let mut body = Vec::new();
+    let mut chars = pattern[body_start..close].iter();
+    while let Some(&c) = chars.next() {
+        match c {
+            '\\' => body.extend(chars.next().map(|&c| (c, true))),
+            c => body.push((c, false)),
+        }
+    }
+
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < body.len() {
+        if i + 2 < body.len() && body[i + 1] == ('-', false) {
+            ranges.push((body[i].0, body[i + 2].0));
+            i += 3;
+        } else {
+            ranges.push((body[i].0, body[i].0));
+            i += 1;
+        }
+    }
+    Some((Token::Class { ranges, negated }, close + 1))
+}
+
+// 把一个分支的 token 翻译为正则（不含锚定）
+fn to_regex(tokens: &[Token]) -> String {
+    let mut regex = String::new();
+    for token in tokens {
+        match token {
+            Token::Literal(c) => regex.push_str(&escape(*c)),
+            Token::AnyChar => regex.push_str("[^/]"),
+            Token::AnyRun => regex.push_str("[^/]*"),
+            Token::AnyDirs => regex.push_str("(?:.*/)?"),
+            Token::AnyPath => regex.push_str(".*"),
+            Token::Class { ranges, negated } => {
+                // 起点大于终点的区间不匹配任何字符
+                let items: String = ranges
+                    .iter()
+                    .filter(|(lo, hi)| lo <= hi)
+                    .map(|&(lo, hi)| match lo == hi {
+                        true => escape(lo),
+                        false => format!("{}-{}", escape(lo), escape(hi)),
+                    })
+                    .collect();
+                // 字符类同样不匹配 `/`
+                match (negated, items.is_empty()) {
+                    (true, _) => regex.push_str(&format!("[^/{}]", items)),
+                    // 空的字符类不匹配任何字符
+                    (false, true) => regex.push_str("[a&&b]"),
+                    (false, false) => regex.push_str(&format!("[{}&&[^/]]", items)),
+                }
+            }
+        }
+    }
+    regex
+}
+
+fn escape(c: char) -> String {
+    regex::escape(c.encode_utf8(&mut [0; 4]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn many_stars_do_not_backtrack_exponentially() {
+        let glob = GlobMatcher::new("*a*a*a*a*a*a*a*a*a*a*a*a*b");
+        let text = "a".repeat(200);
+        let started = Instant::now();
+        assert!(!glob.is_match(&text));
+        assert!(glob.is_match(&format!("{}b", text)));
+        let glob = GlobMatcher::new("**/a*a*a*a*a*a*a*a*/**/b");
+        assert!(!glob.is_match(&format!("x/{}/{}/c", text, text)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn special_characters_are_literal() {
+        assert!(GlobMatcher::new("a+b(c).txt").is_match("a+b(c).txt"));
+        assert!(!GlobMatcher::new("a.txt").is_match("abtxt"));
+        assert!(GlobMatcher::new("[-^&~]x").is_match("&x"));
+        // 没有闭合的 `[`、反向的区间
+        assert!(GlobMatcher::new("a[b").is_match("a[b"));
+        assert!(!GlobMatcher::new("[z-a]").is_match("m"));
+        assert!(GlobMatcher::new("[!z-a]").is_match("m"));
+    }
+
+    #[test]
+    fn double_star_in_the_middle() {
+        let glob = GlobMatcher::new("a/**/b");
+        for path in ["a/b", "a/x/b", "a/x/y/b"] {
+            assert!(glob.is_match(path), "{}", path);
+        }
+        for path in ["a/xb", "ab", "x/a/b", "a/b/c"] {
+            assert!(!glob.is_match(path), "{}", path);
+        }
+    }
+
+    #[test]
+    fn leading_double_star() {
+        let glob = GlobMatcher::new("**/foo");
+        for path in ["foo", "x/foo", "x/y/foo"] {
+            assert!(glob.is_match(path), "{}", path);
+        }
+        for path in ["xfoo", "x/foox", "foo/x"] {
+            assert!(!glob.is_match(path), "{}", path);
+        }
+        assert!(GlobMatcher::new("**/*.rs").is_match("src/lib.rs"));
+    }
+
+    #[test]
+    fn trailing_double_star() {
+        let glob = GlobMatcher::new("foo/**");
+        for path in ["foo/x", "foo/x/y"] {
+            assert!(glob.is_match(path), "{}", path);
+        }
+        for path in ["foo", "foox/y", "x/foo/y"] {
+            assert!(!glob.is_match(path), "{}", path);
+        }
+        // 不成段的 `**` 与 `*` 相同
+        assert!(!GlobMatcher::new("foo**").is_match("foo/x"));
+    }
+
+    #[test]
+    fn negated_class() {
+        let glob = GlobMatcher::new("[!x].txt");
+        assert!(glob.is_match("a.txt"));
+        assert!(!glob.is_match("x.txt"));
+        assert!(!glob.is_match("ab.txt"));
+        // 取反的字符类同样不匹配 `/`
+        assert!(!GlobMatcher::new("a[!x]b").is_match("a/b"));
+        assert!(GlobMatcher::new("[^a-c]").is_match("d"));
+    }
+
+    #[test]
+    fn brace_expansion() {
+        let glob = GlobMatcher::new("*.{rs,md}");
+        assert!(glob.is_match("lib.rs"));
+        assert!(glob.is_match("README.md"));
+        assert!(!glob.is_match("notes.txt"));
+        assert!(!glob.is_match("lib.rsmd"));
+
+        assert_eq!(expand_braces("a{b,c{d,e}}f"), ["abf", "acdf", "acef"]);
+        assert_eq!(expand_braces("{a,b}{1,2}"), ["a1", "a2", "b1", "b2"]);
+        // 空分支、空花括号、没有闭合和转义的花括号
+        assert_eq!(expand_braces("foo{,.bak}"), ["foo", "foo.bak"]);
+        assert_eq!(expand_braces("a{}b"), ["a{}b"]);
+        assert_eq!(expand_braces("a{b,c"), ["a{b,c"]);
+        assert_eq!(expand_braces(r"\{a,b}"), [r"\{a,b}"]);
+        assert!(GlobMatcher::new("a{}b").is_match("a{}b"));
+    }
+
+    #[test]
+    fn escaped_metacharacters_are_literal() {
+        let glob = GlobMatcher::new(r"a\*b");
+        assert!(glob.is_match("a*b"));
+        assert!(!glob.is_match("axb"));
+        assert!(!glob.is_match("ab"));
+        assert!(GlobMatcher::new(r"a\?").is_match("a?"));
+        assert!(!GlobMatcher::new(r"a\?").is_match("ax"));
+        assert!(GlobMatcher::new(r"\[ab\]").is_match("[ab]"));
+        assert!(!GlobMatcher::new(r"\[ab\]").is_match("a"));
+        // 转义普通字符等于字符本身
+        assert!(GlobMatcher::new(r"\x").is_match("x"));
+    }
+}
@@ -0,0 +1,78 @@
+//! 用户的全局忽略文件（git 的 `core.excludesFile`）
+//!
+//! 按 git 的规则查找：
+//! 1. 全局 git 配置中的 `core.excludesFile`：依次读取 `$XDG_CONFIG_HOME/git/config`
+//!    （未设置时为 `~/.config/git/config`）和 `~/.gitconfig`，后读取的覆盖先读取的；
+//! 2. 没有配置时使用默认位置 `$XDG_CONFIG_HOME/git/ignore`（`~/.config/git/ignore`）。
+//!
+//! 不调用 git 命令，只解析配置文件中需要的这一项（不支持 `include` 等指令）。
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// 全局忽略文件的路径；找不到主目录时返回 None（文件本身可能不存在）
+pub(crate) fn excludes_file() -> Option<PathBuf> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let xdg_git_dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("git")),
+        _ => home.as_ref().map(|home| home.join(".config").join("git")),
+    };
+
+    let configs = [
+        xdg_git_dir.as_ref().map(|dir| dir.join("config")),
+        home.as_ref().map(|home| home.join(".gitconfig")),
+    ];
+    // 后读取的配置优先，因此从后往前找第一个设置了该项的文件
+    let configured = configs
+        .iter()
+        .rev()
+        .flatten()
+        .filter_map(|config| fs::read_to_string(config).ok())
+        .find_map(|content| parse_excludes_file(&content));
+    match configured {
+        Some(path) => Some(expand_tilde(&path, home.as_ref())),
+        None => xdg_git_dir.map(|dir| dir.join("ignore")),
+    }
+}
+
+// 从 git 配置文件中找出 `[core]` 段的 `excludesFile`（段名和键名不区分大小写），
+// 多次出现时取最后一次
+fn parse_excludes_file(content: &str) -> Option<String> {
+    let mut in_core = false;
+    let mut value = None;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[') {
+            let name = section.trim_end_matches(']').trim();
+            in_core = name.eq_ignore_ascii_case("core");
+            continue;
+        }
+        if !in_core {
+            continue;
+        }
+        let Some((key, raw)) = line.split_once('=') else {
+            continue;
+        };
+        if key.trim().eq_ignore_ascii_case("excludesfile") {
+            let raw = raw.trim();
+            let raw = raw
+                .strip_prefix('"')
+                .and_then(|raw| raw.strip_suffix('"'))
+                .unwrap_or(raw);
+            value = Some(raw.to_string());
+        }
+    }
+    value
+}
+
+// `~/` 开头的路径相对于主目录
+fn expand_tilde(path: &str, home: Option<&PathBuf>) -> PathBuf {
+    match (path.strip_prefix("~/"), home) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
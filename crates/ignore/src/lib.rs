@@ -1,16 +1,24 @@
 // crates/ignore/src/lib.rs
 
+mod glob;
+mod global;
+mod repo;
+
+pub use glob::GlobMatcher;
+
 use std::path::{Path, PathBuf};
 use std::fs;
 use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 use anyhow::Result;
 
 /// 单个忽略规则
 #[derive(Debug, Clone)]
 struct Pattern {
-    rule: String,           // 原始规则（去除 ! 前缀）
     is_negation: bool,      // 是否为否定规则（!）
     is_directory: bool,    // 是否为目录匹配（以 / 结尾）
+    anchored: bool,        // 去掉结尾的 / 之后仍含有 /：锚定在规则所在目录
+    glob: GlobMatcher,     // 编译好的 glob（去掉结尾的 / 和开头表示锚定的 /）
 }
 
 /// 单个目录的 .gitignore 规则集
@@ -18,27 +26,138 @@ struct Pattern {
 struct IgnoreRules {
     patterns: Vec<Pattern>,
     gitignore_dir: PathBuf, // .gitignore 文件所在目录
+    // 规则实际所在的目录在 gitignore_dir 之上时，gitignore_dir 相对于它的路径
+    // （搜索根目录位于仓库的子目录中时的 .git/info/exclude）；通常为空
+    prefix: PathBuf,
+}
+
+/// 把规则中的路径分隔符统一为 `/`，与 `should_ignore` 中规范化后的路径保持一致
+///
+/// Windows 用户可能写出 `build\output` 这样的规则；其他平台上 `\` 是
+/// gitignore 的转义字符，保持原样。
+fn normalize_separators(rule: &str) -> String {
+    if cfg!(windows) {
+        rule.replace('\\', "/")
+    } else {
+        rule.to_string()
+    }
+}
+
+/// 去掉规则末尾的空白；与 git 一致，用 `\` 转义的空格（`foo\ `）保留
+fn trim_trailing_spaces(line: &str) -> &str {
+    let trimmed = line.trim_end();
+    let backslashes = trimmed.len() - trimmed.trim_end_matches('\\').len();
+    if backslashes % 2 == 1 && trimmed.len() < line.len() {
+        // 保留紧跟在 `\` 之后的那个空白字符
+        let kept = line[trimmed.len()..].chars().next().map_or(0, char::len_utf8);
+        &line[..trimmed.len() + kept]
+    } else {
+        trimmed
+    }
+}
+
+/// 解析 gitignore 格式的规则文件内容
+fn parse_rules(content: &str) -> Vec<Pattern> {
+    let mut patterns = Vec::new();
+    for line in content.lines() {
+        let line = trim_trailing_spaces(line.trim_start());
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // 解析规则（支持否定规则 !）
+        let (is_negation, rule) = if let Some(negated) = line.strip_prefix('!') {
+            (true, normalize_separators(negated.trim()))
+        } else {
+            (false, normalize_separators(line))
+        };
+
+        if !rule.is_empty() {
+            let is_directory = rule.ends_with('/');
+            let rule = rule.trim_end_matches('/');
+            patterns.push(Pattern {
+                is_negation,
+                is_directory,
+                anchored: rule.contains('/'),
+                glob: GlobMatcher::new(rule.trim_start_matches('/')),
+            });
+        }
+    }
+    patterns
+}
+
+/// 每个目录中读取哪些规则文件
+#[derive(Debug, Clone, Copy)]
+pub struct IgnoreConfig {
+    /// 读取版本控制的规则文件：每个目录的 `.gitignore`，以及用户的全局忽略文件
+    /// （`core.excludesFile`，优先级最低）
+    pub vcs: bool,
+    /// 读取 `.ignore`：只影响搜索、不影响 git 的规则，优先级高于同一目录的 `.gitignore`
+    pub dot: bool,
+}
+
+impl Default for IgnoreConfig {
+    fn default() -> Self {
+        IgnoreConfig { vcs: true, dot: true }
+    }
+}
+
+impl IgnoreConfig {
+    /// 是否会读取任何目录中的规则文件
+    pub fn any(&self) -> bool {
+        self.vcs || self.dot
+    }
 }
 
 /// 支持嵌套 .gitignore 的忽略系统
+///
+/// 构造（包括 `add_ignore_file`）完成后只通过 `&self` 匹配，可以在多个线程之间共享
+/// （例如放在 `Arc` 中或直接借用）。各目录的规则文件在第一次用到时加载，
+/// 缓存由读写锁保护：命中缓存时只需要读锁，多个线程可以同时匹配。
 pub struct Ignore {
     root: PathBuf,  // 项目根目录
+    config: IgnoreConfig,
     // 缓存：目录路径 -> 该目录的 .gitignore 规则
-    cache: HashMap<PathBuf, IgnoreRules>,
+    cache: RwLock<HashMap<PathBuf, Arc<IgnoreRules>>>,
+    // 额外的规则集（全局忽略文件、.git/info/exclude，然后是 `--ignore-file`），按添加顺序应用，
+    // 优先级低于所有目录中的规则文件
+    extra: Vec<IgnoreRules>,
 }
 
 impl Ignore {
-    /// 创建空的 Ignore 实例
+    /// 创建空的 Ignore 实例（读取所有类型的规则文件）
     pub fn new(root: PathBuf) -> Self {
-        Ignore {
+        Self::with_config(root, IgnoreConfig::default())
+    }
+
+    /// 创建只读取 `config` 中选中的规则文件的实例
+    pub fn with_config(root: PathBuf, config: IgnoreConfig) -> Self {
+        let mut ignore = Ignore {
             root,
-            cache: HashMap::new(),
+            config,
+            cache: RwLock::new(HashMap::new()),
+            extra: Vec::new(),
+        };
+        // 全局忽略文件通常不存在，读取失败时忽略
+        if config.vcs
+            && let Some(file) = global::excludes_file()
+            && let Ok(content) = fs::read_to_string(file)
+        {
+            ignore.extra.push(IgnoreRules {
+                patterns: parse_rules(&content),
+                gitignore_dir: ignore.root.clone(),
+                prefix: PathBuf::new(),
+            });
+        }
+        if config.vcs {
+            ignore.load_info_exclude();
         }
+        ignore
     }
     
     /// 从根目录的 .gitignore 创建实例（向后兼容）
     pub fn from_gitignore(root: &Path) -> Result<Self> {
-        let mut ignore = Ignore::new(root.to_path_buf());
+        let ignore = Ignore::new(root.to_path_buf());
         let gitignore_path = root.join(".gitignore");
         if gitignore_path.exists() {
             ignore.load_gitignore_for_dir(root)?;
@@ -46,56 +165,82 @@ impl Ignore {
         Ok(ignore)
     }
     
-    /// 为指定目录加载 .gitignore（带缓存）
-    fn load_gitignore_for_dir(&mut self, dir: &Path) -> Result<()> {
+    /// 添加一个 gitignore 格式的规则文件，其中的规则相对于 `root` 解释
+    /// （`root` 应位于根目录之内，否则规则不会命中任何路径）
+    ///
+    /// 规则的优先级低于所有 .gitignore：.gitignore 中的 `!` 规则可以重新包含被它排除的文件。
+    /// 多个文件按添加顺序应用，后添加的覆盖先添加的（以及全局忽略文件）
+    pub fn add_ignore_file(&mut self, file: &Path, root: &Path) -> Result<()> {
+        let content = fs::read_to_string(file)?;
+        self.extra.push(IgnoreRules {
+            patterns: parse_rules(&content),
+            gitignore_dir: root.to_path_buf(),
+            prefix: PathBuf::new(),
+        });
+        Ok(())
+    }
+
+    /// 根目录位于 git 仓库中时加载 `.git/info/exclude`，规则相对于仓库根目录，
+    /// 优先级高于全局忽略文件、低于 .gitignore
+    fn load_info_exclude(&mut self) {
+        // 命令行中不带目录的文件名（`a.txt`）的根目录是空路径，即当前目录
+        let root = match self.root.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => self.root.as_path(),
+        };
+        let Some(repo) = repo::Repository::discover(root) else {
+            return;
+        };
+        let Ok(content) = fs::read_to_string(repo.info_exclude()) else {
+            return;
+        };
+        // 根目录在仓库中的位置，用于把相对于根目录的路径转换为相对于仓库根目录的路径
+        let Some(prefix) = fs::canonicalize(root)
+            .ok()
+            .and_then(|root| root.strip_prefix(&repo.root).ok().map(Path::to_path_buf))
+        else {
+            return;
+        };
+        self.extra.push(IgnoreRules {
+            patterns: parse_rules(&content),
+            gitignore_dir: self.root.clone(),
+            prefix,
+        });
+    }
+
+    /// 为指定目录加载 .gitignore 和 .ignore（带缓存）
+    fn load_gitignore_for_dir(&self, dir: &Path) -> Result<Arc<IgnoreRules>> {
         // 检查缓存
-        if self.cache.contains_key(dir) {
-            return Ok(());
+        if let Some(rules) = self.cache.read().unwrap().get(dir) {
+            return Ok(Arc::clone(rules));
         }
         
-        let gitignore_path = dir.join(".gitignore");
+        // 同一目录中 .ignore 的规则排在 .gitignore 之后，命中时覆盖 .gitignore
         let mut patterns = Vec::new();
-        
-        if gitignore_path.exists() {
-            let content = fs::read_to_string(&gitignore_path)?;
-            for line in content.lines() {
-                let line = line.trim();
-                if line.is_empty() || line.starts_with('#') {
-                    continue;
-                }
-                
-                // 解析规则（支持否定规则 !）
-                let (is_negation, rule) = if line.starts_with('!') {
-                    (true, line[1..].trim().to_string())
-                } else {
-                    (false, line.to_string())
-                };
-                
-                if !rule.is_empty() {
-                    let is_directory = rule.ends_with('/');
-                    patterns.push(Pattern {
-                        rule,
-                        is_negation,
-                        is_directory,
-                    });
-                }
+        let sources = [(".gitignore", self.config.vcs), (".ignore", self.config.dot)];
+        for (name, enabled) in sources {
+            let path = dir.join(name);
+            if enabled && path.exists() {
+                patterns.extend(parse_rules(&fs::read_to_string(&path)?));
             }
         }
         
-        // 存入缓存
-        self.cache.insert(
-            dir.to_path_buf(),
-            IgnoreRules {
-                patterns,
-                gitignore_dir: dir.to_path_buf(),
-            },
-        );
-        
-        Ok(())
+        // 存入缓存；其他线程可能同时加载了同一个目录，保留先存入的那份
+        let rules = Arc::new(IgnoreRules {
+            patterns,
+            gitignore_dir: dir.to_path_buf(),
+            prefix: PathBuf::new(),
+        });
+        let mut cache = self.cache.write().unwrap();
+        Ok(Arc::clone(cache.entry(dir.to_path_buf()).or_insert(rules)))
     }
     
     /// 查找从根目录到指定路径的所有 .gitignore 文件
-    fn find_gitignore_chain(&mut self, path: &Path) -> Result<Vec<PathBuf>> {
+    ///
+    /// 规则链只由路径本身决定（根目录到文件所在目录的每一级），
+    /// 与遍历方式无关：即使 `--max-depth` 限制了遍历深度，
+    /// 被访问文件的每一级祖先目录的 .gitignore 仍会按需加载，且不会越过根目录。
+    fn find_gitignore_chain(&self, path: &Path) -> Result<Vec<Arc<IgnoreRules>>> {
         let mut chain = Vec::new();
         
         // 从根目录开始，向上遍历到文件所在目录
@@ -115,7 +260,7 @@ impl Ignore {
         let mut dir = current;
         loop {
             dirs.push(dir.to_path_buf());
-            if dir == &self.root || dir.parent().is_none() {
+            if dir == self.root || dir.parent().is_none() {
                 break;
             }
             dir = dir.parent().unwrap();
@@ -129,115 +274,266 @@ impl Ignore {
         
         // 加载每个目录的 .gitignore
         for dir in &dirs {
-            self.load_gitignore_for_dir(dir)?;
-            if self.cache.contains_key(dir) {
-                chain.push(dir.clone());
-            }
+            chain.push(self.load_gitignore_for_dir(dir)?);
         }
         
         Ok(chain)
     }
     
     /// 判断路径是否应该被忽略
-    pub fn should_ignore(&mut self, path: &Path) -> bool {
+    ///
+    /// 优先级与 git 一致：
+    /// - 同一个 .gitignore 中靠后的规则覆盖靠前的规则；
+    /// - 子目录的 .gitignore 覆盖父目录的 .gitignore；
+    /// - 父目录被排除后，其中的文件不能再被 `!` 规则重新包含。
+    pub fn should_ignore(&self, path: &Path) -> bool {
+        // 1. 计算相对于根目录的路径
+        let relative_path = match path.strip_prefix(&self.root) {
+            Ok(p) => p.to_path_buf(),
+            Err(_) => return false, // 如果路径不在根目录下，不忽略
+        };
+
+        // 2. 从根目录向下检查每一级祖先目录，任何一级被排除则整个子树被排除
+        if let Some(parent) = relative_path.parent() {
+            let mut ancestor = self.root.clone();
+            for component in parent.components() {
+                ancestor.push(component);
+                if self.matches_rules(&ancestor, true) {
+                    return true;
+                }
+            }
+        }
+
+        // 3. 路径本身
+        self.matches_rules(path, path.is_dir())
+    }
+
+    /// 按 .gitignore 链（从根目录到子目录）依次应用规则，最后命中的规则决定结果
+    fn matches_rules(&self, path: &Path, is_dir: bool) -> bool {
         // 1. 找到所有相关的 .gitignore 文件链
         let gitignore_chain = match self.find_gitignore_chain(path) {
             Ok(chain) => chain,
             Err(_) => return false, // 如果出错，不忽略
         };
-        
+
         // 2. 计算相对于根目录的路径
         let relative_path = match path.strip_prefix(&self.root) {
             Ok(p) => p,
             Err(_) => return false, // 如果路径不在根目录下，不忽略
         };
-        
-        // 3. 按顺序应用规则（从根目录到子目录）
+
+        // 3. 按顺序应用规则：先是额外的规则文件，然后从根目录到子目录
         let mut should_ignore = false;
-        
-        for gitignore_dir in gitignore_chain {
-            if let Some(rules) = self.cache.get(&gitignore_dir) {
-                // 计算相对于该 .gitignore 所在目录的路径
-                let dir_relative = match relative_path.strip_prefix(
-                    gitignore_dir.strip_prefix(&self.root).unwrap_or(&gitignore_dir)
-                ) {
-                    Ok(p) => p,
-                    Err(_) => continue, // 如果路径不在该目录下，跳过
-                };
-                
-                let dir_relative_str = dir_relative.to_string_lossy().replace('\\', "/");
-                
-                // 应用该目录的所有规则
-                for pattern in &rules.patterns {
-                    if self.match_pattern(&pattern.rule, &dir_relative_str, pattern.is_directory) {
-                        if pattern.is_negation {
-                            // 否定规则：取消忽略
-                            should_ignore = false;
-                        } else {
-                            // 普通规则：标记为忽略
-                            should_ignore = true;
-                        }
-                    }
-                }
-            }
+        for rules in &self.extra {
+            self.apply_rules(rules, relative_path, is_dir, &mut should_ignore);
         }
-        
+        for rules in &gitignore_chain {
+            self.apply_rules(rules, relative_path, is_dir, &mut should_ignore);
+        }
+
         should_ignore
     }
-    
-    /// 匹配单个规则（使用相对路径）
-    fn match_pattern(&self, pattern: &str, relative_path: &str, is_directory: bool) -> bool {
-        // 1️⃣ 完全匹配
-        if pattern == relative_path {
-            return true;
+
+    /// 应用一个规则集：命中的规则依次覆盖 `should_ignore`
+    fn apply_rules(&self, rules: &IgnoreRules, relative_path: &Path, is_dir: bool, should_ignore: &mut bool) {
+        // 计算相对于规则所在目录的路径
+        // （Path::strip_prefix 按路径组件比较，Windows 上 `\` 和 `/` 混用也能正确剥离）
+        let dir_relative = match relative_path.strip_prefix(
+            rules.gitignore_dir.strip_prefix(&self.root).unwrap_or(&rules.gitignore_dir)
+        ) {
+            Ok(p) => p,
+            Err(_) => return, // 如果路径不在该目录下，跳过
+        };
+
+        // 目录自己的 .gitignore 只作用于目录里的内容，不作用于目录本身
+        if dir_relative.as_os_str().is_empty() {
+            return;
         }
-        
-        // 2️⃣ 目录匹配（pattern 以 / 结尾）
-        if is_directory {
-            let dir_pattern = pattern.trim_end_matches('/');
-            // 匹配路径中包含该目录的情况
-            if relative_path.contains(&format!("{}/", dir_pattern)) {
-                return true;
-            }
-            // 匹配路径以该目录开头的情况
-            if relative_path.starts_with(dir_pattern) {
-                return true;
+
+        let dir_relative = rules.prefix.join(dir_relative);
+        let dir_relative_str = dir_relative.to_string_lossy().replace('\\', "/");
+
+        // 应用该目录的所有规则
+        for pattern in &rules.patterns {
+            if self.match_pattern(pattern, &dir_relative_str, is_dir) {
+                // 否定规则取消忽略，普通规则标记为忽略
+                *should_ignore = !pattern.is_negation;
             }
-            return false;
         }
-        
-        // 3️⃣ 通配符匹配（简化版）
-        if pattern.contains('*') {
-            // "*" 匹配任意文件名（不跨目录）
-            if pattern == "*" {
-                return !relative_path.contains('/');
-            }
-            
-            // "*.ext"
-            if let Some(ext) = pattern.strip_prefix("*.") {
-                return relative_path.ends_with(ext);
-            }
-            
-            // "prefix*"
-            if let Some(prefix) = pattern.strip_suffix('*') {
-                return relative_path.starts_with(prefix);
-            }
-            
-            // 其他复杂情况，基础版不支持
+    }
+
+    /// 匹配单个规则（使用相对路径）
+    ///
+    /// 目录规则（以 `/` 结尾）只匹配目录本身；目录中的内容由 `should_ignore`
+    /// 通过检查祖先目录来排除。规则的 glob 在解析时编译好，`?`、字符类、`\` 转义
+    /// 和 `**` 的含义见 `glob` 模块。
+    fn match_pattern(&self, pattern: &Pattern, relative_path: &str, path_is_dir: bool) -> bool {
+        // 1️⃣ 目录规则不匹配文件
+        if pattern.is_directory && !path_is_dir {
             return false;
         }
-        
-        // 4️⃣ 文件名匹配（pattern 不包含 /）
-        if !pattern.contains('/') {
-            if let Some(name) = Path::new(relative_path).file_name() {
-                if let Some(name_str) = name.to_str() {
-                    return name_str == pattern;
-                }
+
+        // 2️⃣ 路径匹配（pattern 包含 /）：锚定在 .gitignore 所在目录，
+        //    对整个相对路径做 glob 匹配（`*` 不跨越 `/`，即逐段匹配）；开头的 `/` 只表示锚定
+        if pattern.anchored {
+            return pattern.glob.is_match(relative_path);
+        }
+
+        // 3️⃣ 文件名匹配（pattern 不包含 /）：与任意深度的文件名比较，
+        //    `foo.txt` 既匹配 `foo.txt` 也匹配 `a/b/foo.txt`
+        match Path::new(relative_path).file_name().and_then(|name| name.to_str()) {
+            Some(name) => pattern.glob.is_match(name),
+            None => false,
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // 创建临时目录并写入文件；以 `/` 结尾的路径创建为目录
+    fn tree(files: &[(&str, &str)]) -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            if path.to_string_lossy().ends_with('/') {
+                fs::create_dir_all(&path).unwrap();
+                continue;
             }
-            return false;
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
         }
-        
-        // 5️⃣ 路径匹配（pattern 包含 /）
-        relative_path.contains(pattern)
+        dir
+    }
+
+    fn ignored(dir: &TempDir, path: &str) -> bool {
+        Ignore::new(dir.path().to_path_buf()).should_ignore(&dir.path().join(path))
+    }
+
+    #[test]
+    fn excluded_parent_cannot_be_reincluded() {
+        for rules in ["build/\n!build/keep.txt\n", "build\n!build/keep.txt\n", "build/\n!keep.txt\n"] {
+            let dir = tree(&[(".gitignore", rules), ("build/keep.txt", ""), ("keep.txt", "")]);
+            assert!(ignored(&dir, "build/keep.txt"), "{:?}", rules);
+            assert!(!ignored(&dir, "keep.txt"), "{:?}", rules);
+        }
+    }
+
+    #[test]
+    fn nested_negation_cannot_reinclude_from_excluded_parent() {
+        let dir = tree(&[
+            (".gitignore", "build/\n"),
+            ("build/.gitignore", "!keep.txt\n"),
+            ("build/keep.txt", ""),
+        ]);
+        assert!(ignored(&dir, "build/keep.txt"));
+    }
+
+    #[test]
+    fn negation_reincludes_files_excluded_by_pattern() {
+        // 排除的是文件本身而不是父目录时，`!` 规则可以重新包含
+        let dir = tree(&[(".gitignore", "*.txt\n!keep.txt\n"), ("a/keep.txt", ""), ("a/drop.txt", "")]);
+        assert!(!ignored(&dir, "a/keep.txt"));
+        assert!(ignored(&dir, "a/drop.txt"));
+    }
+
+    #[test]
+    fn slashless_pattern_matches_basename_at_any_depth() {
+        let dir = tree(&[(".gitignore", "foo\n"), ("foo", ""), ("a/foo", ""), ("a/b/c/foo", ""), ("a/foobar", "")]);
+        assert!(ignored(&dir, "foo"));
+        assert!(ignored(&dir, "a/foo"));
+        assert!(ignored(&dir, "a/b/c/foo"));
+        assert!(!ignored(&dir, "a/foobar"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn slashless_directory_pattern_matches_directories_at_any_depth() {
+        let dir = tree(&[
+            (".gitignore", "foo/\n"),
+            ("a/b/foo/x.txt", ""),
+            ("foo/y.txt", ""),
+            ("c/foo", ""),
+        ]);
+        assert!(ignored(&dir, "a/b/foo"));
+        assert!(ignored(&dir, "a/b/foo/x.txt"));
+        assert!(ignored(&dir, "foo/y.txt"));
+        // 目录规则不匹配同名文件
+        assert!(!ignored(&dir, "c/foo"));
+    }
+
+    #[test]
+    fn pattern_with_slash_is_anchored() {
+        let dir = tree(&[(".gitignore", "a/foo\n"), ("a/foo", ""), ("b/a/foo", ""), ("x/a/foo", "")]);
+        assert!(ignored(&dir, "a/foo"));
+        assert!(!ignored(&dir, "b/a/foo"));
+        assert!(!ignored(&dir, "x/a/foo"));
+    }
+
+    #[test]
+    fn glob_with_slash_matches_segments_from_its_directory() {
+        let dir = tree(&[
+            (".gitignore", "doc/*.html\n"),
+            ("doc/a.html", ""),
+            ("doc/sub/b.html", ""),
+            ("src/doc/a.html", ""),
+            ("sub/.gitignore", "doc/*.html\n"),
+            ("sub/doc/c.html", ""),
+        ]);
+        assert!(ignored(&dir, "doc/a.html"));
+        // `*` 不跨越 `/`，规则也不在更深的目录中匹配
+        assert!(!ignored(&dir, "doc/sub/b.html"));
+        assert!(!ignored(&dir, "src/doc/a.html"));
+        // 子目录中的规则相对于它自己的目录锚定
+        assert!(ignored(&dir, "sub/doc/c.html"));
+    }
+
+    #[test]
+    fn nested_gitignore_slashless_pattern_matches_below_its_directory() {
+        let dir = tree(&[("sub/.gitignore", "foo\n"), ("sub/x/y/foo", ""), ("foo", "")]);
+        assert!(ignored(&dir, "sub/x/y/foo"));
+        assert!(!ignored(&dir, "foo"));
+    }
+
+    // Windows 上 `\` 是路径分隔符，文件名中也不能有 `*`
+    #[cfg(unix)]
+    #[test]
+    fn escaped_glob_metacharacters_match_literally() {
+        let dir = tree(&[(".gitignore", "a\\*b\n"), ("a*b", ""), ("axb", "")]);
+        assert!(ignored(&dir, "a*b"));
+        assert!(!ignored(&dir, "axb"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn backslash_separators_in_rules_and_paths() {
+        let dir = tree(&[
+            ("sub/.gitignore", "out\\gen\nlogs\\\n"),
+            ("sub/out/gen/x.txt", ""),
+            ("sub/out/keep.txt", ""),
+            ("sub/logs/y.log", ""),
+        ]);
+        let ignore = Ignore::new(dir.path().to_path_buf());
+        // 规则和路径中的 `\` 与 `/` 混用
+        assert!(ignore.should_ignore(&dir.path().join("sub\\out\\gen")));
+        assert!(ignore.should_ignore(&dir.path().join("sub/out\\gen\\x.txt")));
+        assert!(ignore.should_ignore(&dir.path().join("sub\\logs/y.log")));
+        assert!(!ignore.should_ignore(&dir.path().join("sub\\out\\keep.txt")));
+    }
+
+    #[test]
+    fn linked_worktree_applies_the_main_info_exclude() {
+        let dir = tree(&[
+            ("main/.git/info/exclude", "*.tmp\n"),
+            ("main/.git/worktrees/wt/commondir", "../..\n"),
+            ("wt/a.tmp", ""),
+            ("wt/a.txt", ""),
+        ]);
+        let git_dir = dir.path().join("main/.git/worktrees/wt");
+        fs::write(dir.path().join("wt/.git"), format!("gitdir: {}\n", git_dir.display())).unwrap();
+        let worktree = dir.path().join("wt");
+        let ignore = Ignore::new(worktree.clone());
+        assert!(ignore.should_ignore(&worktree.join("a.tmp")));
+        assert!(!ignore.should_ignore(&worktree.join("a.txt")));
+    }
+}
@@ -0,0 +1,81 @@
+//! git 仓库的发现：从搜索根目录向上查找 `.git`
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 包含某个目录的 git 仓库
+pub(crate) struct Repository {
+    /// 工作区根目录（`.git` 所在的目录，规范化路径）
+    pub root: PathBuf,
+    /// git 目录：通常是 `root/.git`；工作树（worktree）和子模块中 `.git` 是一个文件，
+    /// 内容为 `gitdir: <路径>`，指向真正的 git 目录
+    pub git_dir: PathBuf,
+}
+
+impl Repository {
+    /// 从 `dir` 开始逐级向上查找，返回最近的仓库
+    pub fn discover(dir: &Path) -> Option<Self> {
+        let start = fs::canonicalize(dir).ok()?;
+        start.ancestors().find_map(|root| {
+            let dot_git = root.join(".git");
+            let git_dir = if dot_git.is_dir() {
+                dot_git
+            } else if dot_git.is_file() {
+                let content = fs::read_to_string(&dot_git).ok()?;
+                let target = content.trim().strip_prefix("gitdir:")?.trim();
+                root.join(target)
+            } else {
+                return None;
+            };
+            Some(Repository {
+                root: root.to_path_buf(),
+                git_dir,
+            })
+        })
+    }
+
+    /// 所有工作树共享的 git 目录：链接的工作树（`git worktree add`）的 git 目录位于
+    /// `<主仓库>/.git/worktrees/<名字>`，其中的 `commondir` 文件给出共享目录
+    /// （通常是相对于 git 目录的 `../..`）；普通仓库就是 git 目录本身
+    pub fn common_dir(&self) -> PathBuf {
+        match fs::read_to_string(self.git_dir.join("commondir")) {
+            Ok(content) => self.git_dir.join(content.trim()),
+            Err(_) => self.git_dir.clone(),
+        }
+    }
+
+    /// 仓库本地的排除规则文件，与仓库根目录的 .gitignore 语义相同，但不随仓库提交；
+    /// 工作树共用主仓库的这个文件
+    pub fn info_exclude(&self) -> PathBuf {
+        self.common_dir().join("info").join("exclude")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linked_worktree_uses_the_common_info_exclude() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main");
+        let git_dir = main.join(".git/worktrees/wt");
+        fs::create_dir_all(main.join(".git/info")).unwrap();
+        fs::create_dir_all(&git_dir).unwrap();
+        fs::write(git_dir.join("commondir"), "../..\n").unwrap();
+        let worktree = dir.path().join("wt");
+        fs::create_dir_all(worktree.join("src")).unwrap();
+        fs::write(worktree.join(".git"), format!("gitdir: {}\n", git_dir.display())).unwrap();
+
+        let repo = Repository::discover(&worktree.join("src")).unwrap();
+        assert_eq!(repo.root, fs::canonicalize(&worktree).unwrap());
+        assert_eq!(
+            fs::canonicalize(repo.info_exclude().parent().unwrap()).unwrap(),
+            fs::canonicalize(main.join(".git/info")).unwrap()
+        );
+
+        // 普通仓库没有 commondir
+        let repo = Repository::discover(&main).unwrap();
+        assert_eq!(repo.info_exclude(), fs::canonicalize(&main).unwrap().join(".git/info/exclude"));
+    }
+}
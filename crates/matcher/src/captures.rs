@@ -0,0 +1,93 @@
+/// 一次匹配中各捕获组的位置
+///
+/// 下标 0 是整个匹配，其余为正则中的捕获组（未参与匹配的组为 `None`）。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Captures {
+    spans: Vec<Option<(usize, usize)>>,
+    names: Vec<Option<String>>,
+}
+
+impl Captures {
+    pub fn new(spans: Vec<Option<(usize, usize)>>, names: Vec<Option<String>>) -> Self {
+        Captures { spans, names }
+    }
+
+    /// 只有整个匹配、没有捕获组
+    pub fn whole(start: usize, end: usize) -> Self {
+        Captures {
+            spans: vec![Some((start, end))],
+            names: vec![None],
+        }
+    }
+
+    /// 整个匹配的位置
+    pub fn span(&self) -> (usize, usize) {
+        self.spans[0].unwrap_or((0, 0))
+    }
+
+    /// 第 `index` 个捕获组的位置
+    pub fn get(&self, index: usize) -> Option<(usize, usize)> {
+        self.spans.get(index).copied().flatten()
+    }
+
+    /// 按名字查找捕获组的位置
+    pub fn name(&self, name: &str) -> Option<(usize, usize)> {
+        let index = self.names.iter().position(|n| n.as_deref() == Some(name))?;
+        self.get(index)
+    }
+
+    /// 捕获组数量（包括整个匹配）
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// 按模板展开替换文本，语义与 `regex::Captures::expand` 一致：
+    /// `$1` / `${1}` 引用编号组，`$name` / `${name}` 引用命名组，`$$` 表示字面量 `$`。
+    /// `$name` 会取尽可能长的名字（`$1a` 引用名为 `1a` 的组），需要分隔时使用 `${1}a`。
+    /// 不存在或未参与匹配的组展开为空串。
+    pub fn expand(&self, haystack: &str, template: &str, dst: &mut String) {
+        let mut rest = template;
+        while let Some(dollar) = rest.find('$') {
+            dst.push_str(&rest[..dollar]);
+            rest = &rest[dollar + 1..];
+
+            if let Some(after) = rest.strip_prefix('$') {
+                dst.push('$');
+                rest = after;
+                continue;
+            }
+
+            let (name, after) = if let Some(braced) = rest.strip_prefix('{') {
+                match braced.find('}') {
+                    Some(close) => (&braced[..close], &braced[close + 1..]),
+                    None => ("", rest),
+                }
+            } else {
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                (&rest[..len], &rest[len..])
+            };
+
+            if name.is_empty() {
+                // 不是合法的引用，按字面量输出 `$`
+                dst.push('$');
+                continue;
+            }
+
+            let span = match name.parse::<usize>() {
+                Ok(index) => self.get(index),
+                Err(_) => self.name(name),
+            };
+            if let Some((start, end)) = span {
+                dst.push_str(&haystack[start..end]);
+            }
+            rest = after;
+        }
+        dst.push_str(rest);
+    }
+}
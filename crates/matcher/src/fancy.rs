@@ -0,0 +1,205 @@
+//! 支持环视和反向引用的回溯正则（`-P`），基于 fancy-regex
+//!
+//! 模式中没有这些特性时，fancy-regex 会把整个模式交给 regex crate 执行，速度与
+//! `RegexMatcher` 接近；需要回溯的部分最坏情况下是指数时间，超过回溯上限的行视为不匹配。
+//!
+//! fancy-regex 不接受 `(?-u)`，关闭 Unicode（`--no-pcre2-unicode`）时由这里把 `\w`、`\d`、
+//! `\s`、`\b` 等改写成等价的 ASCII 形式。
+
+use anyhow::Result;
+use fancy_regex::Regex;
+
+use crate::{Captures, Match, MatchScope, Matcher, collapse_empty_matches};
+
+pub struct FancyRegexMatcher {
+    regex: Regex,
+}
+
+impl FancyRegexMatcher {
+    pub fn new(pattern: &str) -> Result<Self> {
+        Self::with_scope(pattern, MatchScope::Any, false, true)
+    }
+
+    /// 只报告 `scope` 范围内的匹配；`multi_line` 时 `^` / `$` 匹配每一行的行首和行尾（`-U`）。
+    /// `unicode` 为 false 时单词、数字、空白字符类和单词边界只认 ASCII 字符
+    pub fn with_scope(pattern: &str, scope: MatchScope, multi_line: bool, unicode: bool) -> Result<Self> {
+        // fancy-regex 不支持 `\b{start-half}` 这样的半边界，用等价的环视表达
+        let wrapped = match scope {
+            MatchScope::Word => format!(r"(?<!\w)(?:{})(?!\w)", pattern),
+            scope => scope.wrap(pattern),
+        };
+        // 标志放在最外层，`-x` 的包装同样按行匹配
+        let wrapped = match multi_line {
+            true => format!("(?m){}", wrapped),
+            false => wrapped,
+        };
+        // `-w` 的包装同样使用 ASCII 的单词字符
+        let wrapped = match unicode {
+            true => wrapped,
+            false => ascii_classes(&wrapped),
+        };
+        Ok(Self {
+            regex: Regex::new(&wrapped)?,
+        })
+    }
+}
+
+// ASCII 单词边界：一侧是单词字符，另一侧不是
+const ASCII_WORD_BOUNDARY: &str = r"(?:(?<=[[:word:]])(?![[:word:]])|(?<![[:word:]])(?=[[:word:]]))";
+const ASCII_NOT_WORD_BOUNDARY: &str = r"(?:(?<=[[:word:]])(?=[[:word:]])|(?<![[:word:]])(?![[:word:]]))";
+
+// 把 Unicode 的字符类简写和单词边界改写为 ASCII 版本，其余部分原样保留
+fn ascii_classes(pattern: &str) -> String {
+    let mut result = String::with_capacity(pattern.len());
+    // 方括号字符类的嵌套深度；类中的简写改写为 POSIX 类名（不带外层方括号）
+    let mut class_depth = 0;
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let Some(next) = chars.next() else {
+                    result.push(c);
+                    break;
+                };
+                let posix = match next {
+                    'w' => Some("[:word:]"),
+                    'W' => Some("[:^word:]"),
+                    'd' => Some("[:digit:]"),
+                    'D' => Some("[:^digit:]"),
+                    's' => Some("[:space:]"),
+                    'S' => Some("[:^space:]"),
+                    _ => None,
+                };
+                match (posix, next) {
+                    (Some(posix), _) if class_depth > 0 => result.push_str(posix),
+                    (Some(posix), _) => {
+                        result.push('[');
+                        result.push_str(posix);
+                        result.push(']');
+                    }
+                    (None, 'b') if class_depth == 0 => result.push_str(ASCII_WORD_BOUNDARY),
+                    (None, 'B') if class_depth == 0 => result.push_str(ASCII_NOT_WORD_BOUNDARY),
+                    (None, _) => {
+                        result.push(c);
+                        result.push(next);
+                    }
+                }
+            }
+            '[' => {
+                result.push(c);
+                // 开头的 `^` 和紧随其后的 `]` 属于这个类本身
+                if class_depth == 0 {
+                    if chars.peek() == Some(&'^') {
+                        result.push(chars.next().unwrap());
+                    }
+                    if chars.peek() == Some(&']') {
+                        result.push(chars.next().unwrap());
+                    }
+                }
+                class_depth += 1;
+            }
+            ']' if class_depth > 0 => {
+                result.push(c);
+                class_depth -= 1;
+            }
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+impl Matcher for FancyRegexMatcher {
+    fn find_matches(&self, haystack: &str) -> Vec<Match> {
+        // 回溯超过上限时返回错误，之后的部分不再报告匹配
+        let matches = self
+            .regex
+            .find_iter(haystack)
+            .map_while(|mat| mat.ok())
+            .map(|mat| Match::new(mat.start(), mat.end(), 0, mat.as_str().to_string()))
+            .collect();
+        collapse_empty_matches(haystack, matches)
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        self.regex.is_match(haystack).unwrap_or(false)
+    }
+
+    fn captures(&self, haystack: &str) -> Vec<Captures> {
+        let names: Vec<Option<String>> = self
+            .regex
+            .capture_names()
+            .map(|name| name.map(str::to_string))
+            .collect();
+        self.regex
+            .captures_iter(haystack)
+            .map_while(|caps| caps.ok())
+            .map(|caps| {
+                let spans = caps
+                    .iter()
+                    .map(|group| group.map(|m| (m.start(), m.end())))
+                    .collect();
+                Captures::new(spans, names.clone())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(matcher: &FancyRegexMatcher, haystack: &str) -> Vec<(usize, usize)> {
+        matcher.find_matches(haystack).iter().map(|m| (m.start, m.end)).collect()
+    }
+
+    #[test]
+    fn look_around_and_backreferences() {
+        let matcher = FancyRegexMatcher::new(r"(?<=\$)\d+").unwrap();
+        assert_eq!(spans(&matcher, "cost $42, 17 items"), [(6, 8)]);
+
+        let matcher = FancyRegexMatcher::new(r"\b(\w+) \1\b").unwrap();
+        assert!(matcher.is_match("it is is here"));
+        assert!(!matcher.is_match("it is here"));
+        let captures = matcher.captures("it is is here");
+        assert_eq!(captures[0].get(1), Some((3, 5)));
+    }
+
+    #[test]
+    fn scopes() {
+        let word = FancyRegexMatcher::with_scope("-x", MatchScope::Word, false, true).unwrap();
+        assert_eq!(spans(&word, "a -x b -xy"), [(2, 4)]);
+
+        let line = FancyRegexMatcher::with_scope("fo+", MatchScope::Line, false, true).unwrap();
+        assert!(line.is_match("foo"));
+        assert!(!line.is_match("food"));
+
+        let multi_line = FancyRegexMatcher::with_scope("foo", MatchScope::Line, true, true).unwrap();
+        assert_eq!(spans(&multi_line, "bar\nfoo\nfoox\n"), [(4, 7)]);
+    }
+
+    #[test]
+    fn without_unicode_classes_are_ascii() {
+        let unicode = FancyRegexMatcher::new(r"\w+").unwrap();
+        let ascii = FancyRegexMatcher::with_scope(r"\w+", MatchScope::Any, false, false).unwrap();
+        assert_eq!(spans(&unicode, "café au lait"), [(0, 5), (6, 8), (9, 13)]);
+        assert_eq!(spans(&ascii, "café au lait"), [(0, 3), (6, 8), (9, 13)]);
+
+        // 单词边界和 -w 同样按 ASCII 判断：é 不是单词字符
+        let boundary = FancyRegexMatcher::with_scope(r"caf\b", MatchScope::Any, false, false).unwrap();
+        assert!(boundary.is_match("café"));
+        assert!(!FancyRegexMatcher::new(r"caf\b").unwrap().is_match("café"));
+        let word = FancyRegexMatcher::with_scope("caf", MatchScope::Word, false, false).unwrap();
+        assert!(word.is_match("café"));
+
+        let digits = FancyRegexMatcher::with_scope(r"[\d-]+", MatchScope::Any, false, false).unwrap();
+        assert_eq!(spans(&digits, "١٢ 3-4"), [(5, 8)]);
+        let not_space = FancyRegexMatcher::with_scope(r"\S+", MatchScope::Any, false, false).unwrap();
+        assert_eq!(spans(&not_space, "a\u{a0}b"), [(0, 4)]);
+    }
+
+    #[test]
+    fn ascii_rewrite_keeps_other_syntax() {
+        assert_eq!(ascii_classes(r"\.\\w[]\w][^\W]"), r"\.\\w[][:word:]][^[:^word:]]");
+        assert_eq!(ascii_classes(r"[[:alpha:]\s]\d"), r"[[:alpha:][:space:]][[:digit:]]");
+    }
+}
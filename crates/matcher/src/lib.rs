@@ -1,14 +1,25 @@
-use regex::Regex;
+mod captures;
+mod fancy;
+
+use regex::{Regex, RegexBuilder};
+use aho_corasick::{AhoCorasick, Anchored, Input, MatchKind, StartKind};
 use anyhow::Result;
 use memchr::memmem::Finder;
-use memchr;
+use std::cmp::{Ordering, Reverse};
 use std::collections::HashMap;
 
+pub use captures::Captures;
+pub use fancy::FancyRegexMatcher;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Match {
     pub start: usize,
     pub end: usize,
     pub line: usize,
     pub content: String,
+    /// 参与匹配的捕获组（组 1 及以后）的字节范围，与 `start` / `end` 使用同一坐标；
+    /// 只有在需要时（例如高亮捕获组）才会填充
+    pub groups: Vec<(usize, usize)>,
 }
 
 impl Match {
@@ -18,13 +29,64 @@ impl Match {
             end,
             line,
             content,
+            groups: Vec::new(),
         }
     }
 }
 
+/// 按行号、起始位置、结束位置排序；位置完全相同时再比较内容，与相等性保持一致
+impl Ord for Match {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.line, self.start, self.end, &self.content, &self.groups)
+            .cmp(&(other.line, other.start, other.end, &other.content, &other.groups))
+    }
+}
+
+impl PartialOrd for Match {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 pub trait Matcher {
     fn find_matches(&self, haystack: &str) -> Vec<Match>;
     fn is_match(&self, haystack: &str) -> bool;
+
+    /// 返回每个匹配的捕获组位置，用于 `--replace` 展开替换模板。
+    /// 默认实现只提供整个匹配（组 0）
+    fn captures(&self, haystack: &str) -> Vec<Captures> {
+        self.find_matches(haystack)
+            .into_iter()
+            .map(|m| Captures::whole(m.start, m.end))
+            .collect()
+    }
+
+    /// 在任意字节（例如二进制文件）上查找匹配，返回字节范围，用于 `--hex`。
+    /// 默认实现只能处理合法的 UTF-8
+    fn find_bytes(&self, haystack: &[u8]) -> Vec<(usize, usize)> {
+        match std::str::from_utf8(haystack) {
+            Ok(text) => self.find_matches(text).iter().map(|m| (m.start, m.end)).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+impl<M: Matcher + ?Sized> Matcher for Box<M> {
+    fn find_matches(&self, haystack: &str) -> Vec<Match> {
+        (**self).find_matches(haystack)
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        (**self).is_match(haystack)
+    }
+
+    fn captures(&self, haystack: &str) -> Vec<Captures> {
+        (**self).captures(haystack)
+    }
+
+    fn find_bytes(&self, haystack: &[u8]) -> Vec<(usize, usize)> {
+        (**self).find_bytes(haystack)
+    }
 }
 
 // 字面量提取辅助函数
@@ -38,6 +100,12 @@ fn is_special_byte(b: u8) -> bool {
 }
 
 fn select_rare_byte(pattern: &str) -> Option<u8> {
+    // 0. 只有每个字面量字节都必须出现时才能用来跳过：分支、可选量词、
+    //    字符类和转义（`\d`、`(?i)` 等）都会让模式中的字节变成可选的
+    if pattern.contains(['|', '?', '*', '{', '[', '\\']) {
+        return None;
+    }
+
     // 1. 提取字面量字节
     let bytes: Vec<u8> = pattern
         .bytes()
@@ -60,35 +128,86 @@ fn select_rare_byte(pattern: &str) -> Option<u8> {
         .map(|(&byte, _)| byte);
     
     // 4. 检查是否足够稀有（频率 <= 5）
-    if let Some(byte) = rare_byte {
-        if freq[&byte] <= 5 {
-            return Some(byte);
+    rare_byte.filter(|byte| freq[byte] <= 5)
+}
+
+/// 模式中不含任何正则元字符，可以按固定字符串匹配
+pub fn is_pure_literal(pattern: &str) -> bool {
+    !pattern.chars().any(is_special_char)
+}
+
+// 模式中是否包含行锚点 `^` / `$`（忽略转义和字符类中的情况）
+fn has_anchor(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    let mut in_class = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '[' => in_class = true,
+            ']' => in_class = false,
+            '^' | '$' if !in_class => return true,
+            _ => {}
         }
     }
-    
-    None
+    false
 }
 
-fn is_pure_literal(pattern: &str) -> bool {
-    !pattern.chars().any(is_special_char)
+// 按最外层的 `|` 拆分模式（忽略转义、字符类和分组中的 `|`）
+fn split_alternation(pattern: &str) -> Vec<&str> {
+    let mut alternatives = Vec::new();
+    let mut depth = 0;
+    let mut in_class = false;
+    let mut start = 0;
+    let mut chars = pattern.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '[' if !in_class => in_class = true,
+            ']' if in_class => in_class = false,
+            '(' if !in_class => depth += 1,
+            ')' if !in_class => depth -= 1,
+            '|' if !in_class && depth == 0 => {
+                alternatives.push(&pattern[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    alternatives.push(&pattern[start..]);
+    alternatives
 }
 
 fn extract_prefix(pattern: &str) -> String {
     let mut prefix = String::new();
-    let mut chars = pattern.chars().peekable();
+    // 最外层有分支时，第一个分支的前缀不是每个匹配都必须包含的
+    if split_alternation(pattern).len() > 1 {
+        return prefix;
+    }
+    // 行首锚点 `^` 是零宽的，不属于字面量
+    let mut chars = pattern.strip_prefix('^').unwrap_or(pattern).chars().peekable();
     
-    while let Some(c) = chars.peek() {
+    while let Some(&c) = chars.peek() {
         match c {
-            '.' | '*' | '+' | '?' | '{' | '[' | '(' | '|' => break,
+            // 前一个字符可以出现零次，不属于必需的前缀
+            '*' | '?' | '{' => {
+                prefix.pop();
+                break;
+            }
+            '.' | '+' | '[' | '(' | '|' | '^' | '$' => break,
             '\\' => {
-                // 处理转义字符
+                // 只有转义的标点是字面量；`\d`、`\w`、`\b` 等是字符类或断言
                 chars.next();
-                if let Some(escaped) = chars.next() {
-                    prefix.push(escaped);
+                match chars.next() {
+                    Some(escaped) if escaped.is_ascii_punctuation() => prefix.push(escaped),
+                    _ => break,
                 }
             }
             _ => {
-                prefix.push(*c);
+                prefix.push(c);
                 chars.next();
             }
         }
@@ -113,49 +232,170 @@ fn extract_literals(pattern: &str) -> Option<String> {
     None
 }
 
+// 识别行首锚定的字面量分支：`^(GET|POST|PUT) ...` / `^(?:GET|POST) ...`，
+// 返回各分支的字面量。任一分支含有元字符或为空时返回 None；
+// 模式最外层还有其他分支（`^(GET|POST)|foo`）时，匹配的行不一定以这些分支开头，同样返回 None
+fn extract_anchored_alternatives(pattern: &str) -> Option<Vec<String>> {
+    if split_alternation(pattern).len() > 1 {
+        return None;
+    }
+    let rest = pattern.strip_prefix("^(")?;
+    let rest = rest.strip_prefix("?:").unwrap_or(rest);
+    // 分支本身不含元字符，第一个 `)` 就是分组的结尾
+    let close = rest.find(')')?;
+    let alternatives: Vec<String> = rest[..close].split('|').map(str::to_string).collect();
+    // 分组后面跟着量词时分支可以不出现
+    if matches!(rest[close + 1..].chars().next(), Some('*' | '?' | '{')) {
+        return None;
+    }
+    if alternatives.iter().any(|alt| alt.is_empty() || !is_pure_literal(alt)) {
+        return None;
+    }
+    Some(alternatives)
+}
+
+/// 模式必须匹配的范围
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MatchScope {
+    /// 行中任意位置（默认）
+    #[default]
+    Any,
+    /// 完整的单词（`-w`）：匹配之前是行首或非单词字符，之后是行尾或非单词字符
+    Word,
+    /// 整行（`-x`）：匹配必须从行首开始、到行尾结束
+    Line,
+}
+
+impl MatchScope {
+    /// 把模式包装为只在该范围内匹配的正则；模式作为一个整体（非捕获分组）参与匹配，
+    /// 其中的分支和开头的内联标志不会影响包装的部分
+    pub fn wrap(self, pattern: &str) -> String {
+        match self {
+            MatchScope::Any => pattern.to_string(),
+            // 与 `\b` 不同，半边界只检查外侧：`-w -- '-x'` 可以匹配 `a -x b` 中的 `-x`
+            MatchScope::Word => format!(r"\b{{start-half}}(?:{})\b{{end-half}}", pattern),
+            // 搜索器逐行调用匹配器，`^` / `$` 就是行首和行尾
+            MatchScope::Line => format!("^(?:{})$", pattern),
+        }
+    }
+}
+
+/// 正则匹配器：先用从模式中提取的字面量、稀有字节或行首分支快速排除不可能匹配的行，
+/// 再用正则验证。这些预过滤都区分大小写；模式以内联标志（如 `(?i)`）开头时不会启用
 pub struct RegexMatcher {
     regex: Regex,
-    literal: Option<String>,
     literal_finder: Option<Finder<'static>>,
+    // `^(GET|POST|PUT)` 这样的模式：行必须以其中一个分支开头
+    anchored_prefixes: Option<AhoCorasick>,
     rare_byte: Option<u8>,
+    // 模式包含 `^` / `$` 或者限定了匹配范围时，滑动窗口会制造假的行首/行尾或单词边界，
+    // 不能使用窗口
+    anchored: bool,
 }
 
 impl RegexMatcher {
+    /// 把 `text` 当作固定字符串匹配（`-F`）
+    pub fn new_literal(text: &str) -> Result<Self> {
+        Self::new(&regex::escape(text))
+    }
+
     pub fn new(pattern: &str) -> Result<Self> {
-        let regex = Regex::new(pattern)?;
+        Self::with_scope(pattern, MatchScope::Any)
+    }
+
+    /// 只报告 `scope` 范围内的匹配。预过滤从原始模式中提取：包装只增加零宽断言，
+    /// 每个匹配仍然包含原始模式要求的字面量
+    pub fn with_scope(pattern: &str, scope: MatchScope) -> Result<Self> {
+        Self::build(pattern, scope, false)
+    }
+
+    /// 多行搜索（`-U`）：待匹配的文本包含多行，`^` / `$` 匹配每一行的行首和行尾。
+    /// 标志设置在整个正则上，`-x` 的包装同样按行匹配
+    pub fn multi_line(pattern: &str, scope: MatchScope) -> Result<Self> {
+        Self::build(pattern, scope, true)
+    }
+
+    fn build(pattern: &str, scope: MatchScope, multi_line: bool) -> Result<Self> {
+        let regex = RegexBuilder::new(&scope.wrap(pattern))
+            .multi_line(multi_line)
+            .build()?;
         
         // 提取字面量
         let literal = extract_literals(pattern);
-        let literal_finder = literal.as_ref().map(|lit| {
-            // 使用 Box::leak 将字面量转换为 'static 生命周期
-            let leaked = Box::leak(lit.clone().into_boxed_str());
-            Finder::new(leaked.as_bytes())
-        });
+        // Finder 持有字面量的副本，模式很多（`-f`）时也不会泄漏内存
+        let literal_finder = literal.as_ref().map(|lit| Finder::new(lit.as_bytes()).into_owned());
         
+        // 行首锚定的字面量分支，用锚定的多字面量自动机预过滤；
+        // 多行时 `^` 不只是文本的开头，不能使用
+        let anchored_prefixes = match extract_anchored_alternatives(pattern) {
+            Some(alternatives) if !multi_line => Some(
+                AhoCorasick::builder()
+                    .start_kind(StartKind::Anchored)
+                    .build(&alternatives)?,
+            ),
+            _ => None,
+        };
+
         // 选择稀有字节（如果没有字面量，或者作为补充优化）
         let rare_byte = select_rare_byte(pattern);
+        // 多行的匹配可能比窗口更长，同样只能在整段文本上验证
+        let anchored = has_anchor(pattern) || scope != MatchScope::Any || multi_line;
         
         Ok(Self {
             regex,
-            literal,
             literal_finder,
+            anchored_prefixes,
             rare_byte,
+            anchored,
         })
     }
     
+    // 对整段文本运行正则
+    fn find_all(&self, haystack: &str) -> Vec<Match> {
+        self.regex.find_iter(haystack).map(|mat| {
+            Match::new(
+                mat.start(),
+                mat.end(),
+                0, // line will be filled by Searcher
+                mat.as_str().to_string(),
+            )
+        }).collect()
+    }
+
+    // 行首锚定分支的预过滤：行不以任何一个分支开头时一定不匹配
+    fn rejected_by_prefixes(&self, haystack: &str) -> bool {
+        self.anchored_prefixes
+            .as_ref()
+            .is_some_and(|prefixes| !prefixes.is_match(Input::new(haystack).anchored(Anchored::Yes)))
+    }
+
     // 使用稀有字节跳过的辅助方法
     fn find_matches_with_rare_byte(&self, haystack: &str, rare_byte: u8) -> Vec<Match> {
+        // 锚点依赖真实的行首/行尾，只能在整行上验证
+        if self.anchored {
+            if memchr::memchr(rare_byte, haystack.as_bytes()).is_none() {
+                return Vec::new();
+            }
+            return self.find_all(haystack);
+        }
+
         let mut matches = Vec::new();
         let mut pos = 0;
         let window_size = 200; // 固定窗口大小
         
         // 搜索稀有字节
-        while let Some(byte_pos) = memchr::memchr(rare_byte, haystack[pos..].as_bytes()) {
+        while let Some(byte_pos) = memchr::memchr(rare_byte, &haystack.as_bytes()[pos..]) {
             let candidate_pos = pos + byte_pos;
             
-            // 提取候选位置周围的文本（滑动窗口）
-            let start = candidate_pos.saturating_sub(window_size);
-            let end = (candidate_pos + window_size).min(haystack.len());
+            // 提取候选位置周围的文本（滑动窗口，对齐到字符边界）
+            let mut start = candidate_pos.saturating_sub(window_size);
+            while !haystack.is_char_boundary(start) {
+                start -= 1;
+            }
+            let mut end = (candidate_pos + window_size).min(haystack.len());
+            while !haystack.is_char_boundary(end) {
+                end += 1;
+            }
             let candidate = &haystack[start..end];
             
             // 使用正则验证
@@ -174,30 +414,24 @@ impl RegexMatcher {
             pos = candidate_pos + 1;
         }
         
-        // 去重：按位置排序并去重
-        matches.sort_by_key(|m| (m.start, m.end));
-        matches.dedup_by(|a, b| a.start == b.start && a.end == b.end);
-        
-        matches
+        // 去重：按位置排序（同一起点优先保留更长的匹配），丢弃重叠的匹配
+        matches.sort_by_key(|m| (m.start, Reverse(m.end)));
+        remove_overlapping(matches)
     }
-}
 
-impl Matcher for RegexMatcher {
-    fn find_matches(&self, haystack: &str) -> Vec<Match> {
+    // 经过预过滤的正则匹配（零宽匹配尚未合并）
+    fn find_candidates(&self, haystack: &str) -> Vec<Match> {
+        if self.rejected_by_prefixes(haystack) {
+            return Vec::new();
+        }
+
         // 1. 如果有字面量，使用字面量预过滤
         if let Some(ref finder) = self.literal_finder {
             // 使用字面量预过滤：先检查字面量是否存在
             // 因为 Searcher 已经逐行处理，如果字面量存在，验证整行
             if finder.find_iter(haystack.as_bytes()).next().is_some() {
                 // 字面量存在，验证整行是否匹配正则
-                return self.regex.find_iter(haystack).map(|mat| {
-                    Match::new(
-                        mat.start(),
-                        mat.end(),
-                        0, // line will be filled by Searcher
-                        mat.as_str().to_string(),
-                    )
-                }).collect();
+                return self.find_all(haystack);
             } else {
                 // 字面量不存在，直接返回空结果（快速跳过）
                 return Vec::new();
@@ -209,18 +443,77 @@ impl Matcher for RegexMatcher {
             return self.find_matches_with_rare_byte(haystack, rare_byte);
         }
         
-        // 3. 既没有字面量也没有稀有字节，直接使用正则
-        self.regex.find_iter(haystack).map(|mat| {
-            Match::new(
-                mat.start(),
-                mat.end(),
-                0, // line will be filled by Searcher
-                mat.as_str().to_string(),
-            )
-        }).collect()
+        // 3. 既没有字面量也没有稀有字节（例如只有锚点的 `^` / `$`），直接使用正则。
+        //    每行只会得到一个零宽匹配：`^` 位于列 0，`$` 位于行尾
+        self.find_all(haystack)
+    }
+}
+
+// 窗口之间会重叠，同一段文本可能被多次匹配。
+// 与 `Regex::find_iter` 的语义保持一致：匹配互不重叠，
+// 并且紧跟在上一个匹配之后的零宽匹配不会被报告。
+fn remove_overlapping(matches: Vec<Match>) -> Vec<Match> {
+    let mut result: Vec<Match> = Vec::with_capacity(matches.len());
+    for m in matches {
+        if let Some(last) = result.last() {
+            if m.start < last.end {
+                continue;
+            }
+            if m.start == last.end && m.start == m.end {
+                continue;
+            }
+        }
+        result.push(m);
+    }
+    result
+}
+
+/// 能匹配空串的模式（空模式、`a|`、`(?:)`、`x*` 等）在每个位置都会产生零宽匹配。
+/// 为了不让计数和 JSON 输出被大量零宽匹配淹没，一行中的匹配按以下规则合并：
+/// - 有非空匹配时只保留非空匹配（`a|` 在 `xax` 中只报告 `a`）；
+/// - 只有零宽匹配时保留第一个，即每行恰好命中一次（空模式位于第 1 列）。
+///
+/// 多行搜索时 `haystack` 包含多行，匹配按起点所在的行分别合并。
+/// 只影响报告的匹配；`captures`（`--replace`）仍按正则的原始语义替换每个匹配。
+fn collapse_empty_matches(haystack: &str, matches: Vec<Match>) -> Vec<Match> {
+    if matches.iter().all(|m| m.start < m.end) {
+        return matches;
+    }
+    let mut result = Vec::with_capacity(matches.len());
+    let mut line: Vec<Match> = Vec::new();
+    // 当前这组匹配所在行的行尾（下一个换行符的位置）
+    let mut line_end = None;
+    for m in matches {
+        let end = haystack[m.start..].find('\n').map(|i| m.start + i);
+        if line_end != Some(end) {
+            collapse_line(&mut line, &mut result);
+            line_end = Some(end);
+        }
+        line.push(m);
+    }
+    collapse_line(&mut line, &mut result);
+    result
+}
+
+// 合并同一行中的匹配，追加到 result
+fn collapse_line(line: &mut Vec<Match>, result: &mut Vec<Match>) {
+    if line.iter().any(|m| m.start < m.end) {
+        result.extend(line.drain(..).filter(|m| m.start < m.end));
+    } else {
+        result.extend(line.drain(..).take(1));
+    }
+}
+
+impl Matcher for RegexMatcher {
+    fn find_matches(&self, haystack: &str) -> Vec<Match> {
+        collapse_empty_matches(haystack, self.find_candidates(haystack))
     }
 
     fn is_match(&self, haystack: &str) -> bool {
+        if self.rejected_by_prefixes(haystack) {
+            return false;
+        }
+
         // 1. 如果有字面量，先检查字面量是否存在
         if let Some(ref finder) = self.literal_finder {
             if finder.find_iter(haystack.as_bytes()).next().is_some() {
@@ -247,4 +540,453 @@ impl Matcher for RegexMatcher {
         // 3. 既没有字面量也没有稀有字节，直接使用正则
         self.regex.is_match(haystack)
     }
+
+    fn captures(&self, haystack: &str) -> Vec<Captures> {
+        // 预过滤失败时跳过捕获组的计算
+        if !self.is_match(haystack) {
+            return Vec::new();
+        }
+        let names: Vec<Option<String>> = self
+            .regex
+            .capture_names()
+            .map(|name| name.map(str::to_string))
+            .collect();
+        self.regex
+            .captures_iter(haystack)
+            .map(|caps| {
+                let spans = caps
+                    .iter()
+                    .map(|group| group.map(|m| (m.start(), m.end())))
+                    .collect();
+                Captures::new(spans, names.clone())
+            })
+            .collect()
+    }
+}
+
+/// 固定字符串匹配器（`-F`）：不编译正则，直接用 memmem 查找子串
+///
+/// 匹配互不重叠，从左到右报告；空字符串在每行的第 1 列命中一次。
+/// 同样可以在任意字节上查找（`--hex`）。
+pub struct LiteralMatcher {
+    finder: Finder<'static>,
+}
+
+impl LiteralMatcher {
+    pub fn new(literal: &str) -> Self {
+        Self {
+            finder: Finder::new(literal.as_bytes()).into_owned(),
+        }
+    }
+}
+
+impl Matcher for LiteralMatcher {
+    fn find_matches(&self, haystack: &str) -> Vec<Match> {
+        // 匹配的是完整的 UTF-8 子串，起止位置一定在字符边界上
+        let matches = self
+            .find_bytes(haystack.as_bytes())
+            .into_iter()
+            .map(|(start, end)| Match::new(start, end, 0, haystack[start..end].to_string()))
+            .collect();
+        collapse_empty_matches(haystack, matches)
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        self.finder.find(haystack.as_bytes()).is_some()
+    }
+
+    fn find_bytes(&self, haystack: &[u8]) -> Vec<(usize, usize)> {
+        let len = self.finder.needle().len();
+        self.finder
+            .find_iter(haystack)
+            .map(|start| (start, start + len))
+            .collect()
+    }
+}
+
+/// 按字节匹配的正则（`--hex`），可以搜索不是 UTF-8 的二进制数据
+///
+/// 模式中可以用 `(?-u)\xNN` 匹配任意字节；这样的模式无法编译为 `RegexMatcher`。
+pub struct BytesRegexMatcher {
+    regex: regex::bytes::Regex,
+}
+
+impl BytesRegexMatcher {
+    pub fn new(pattern: &str) -> Result<Self> {
+        Self::with_scope(pattern, MatchScope::Any)
+    }
+
+    /// 只报告 `scope` 范围内的匹配
+    pub fn with_scope(pattern: &str, scope: MatchScope) -> Result<Self> {
+        Ok(Self {
+            regex: regex::bytes::Regex::new(&scope.wrap(pattern))?,
+        })
+    }
+
+    /// 把 `text` 当作固定字符串匹配（`-F`）
+    pub fn new_literal(text: &str) -> Result<Self> {
+        Self::new(&regex::escape(text))
+    }
+}
+
+impl Matcher for BytesRegexMatcher {
+    fn find_matches(&self, haystack: &str) -> Vec<Match> {
+        // 字节模式的匹配可能不在字符边界上，内容按有损方式转换
+        let matches = self
+            .find_bytes(haystack.as_bytes())
+            .into_iter()
+            .map(|(start, end)| {
+                let text = String::from_utf8_lossy(&haystack.as_bytes()[start..end]).into_owned();
+                Match::new(start, end, 0, text)
+            })
+            .collect();
+        collapse_empty_matches(haystack, matches)
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        self.regex.is_match(haystack.as_bytes())
+    }
+
+    fn find_bytes(&self, haystack: &[u8]) -> Vec<(usize, usize)> {
+        self.regex
+            .find_iter(haystack)
+            .map(|mat| (mat.start(), mat.end()))
+            .collect()
+    }
+}
+
+/// 组合多个匹配器，任意一个命中即视为匹配（OR 语义）
+///
+/// 需要组合不同类型的匹配器时，可以使用 `OrMatcher<Box<dyn Matcher>>`。
+pub struct OrMatcher<M: Matcher> {
+    matchers: Vec<M>,
+}
+
+impl<M: Matcher> OrMatcher<M> {
+    pub fn new(matchers: Vec<M>) -> Self {
+        Self { matchers }
+    }
+}
+
+impl<M: Matcher> Matcher for OrMatcher<M> {
+    fn find_matches(&self, haystack: &str) -> Vec<Match> {
+        // 合并所有子匹配器的结果，按位置排序后去掉重复和重叠的部分
+        let mut matches: Vec<Match> = self
+            .matchers
+            .iter()
+            .flat_map(|m| m.find_matches(haystack))
+            .collect();
+        matches.sort_by_key(|m| (m.start, Reverse(m.end)));
+        collapse_empty_matches(haystack, remove_overlapping(matches))
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        // 第一个命中的子匹配器即可短路返回
+        self.matchers.iter().any(|m| m.is_match(haystack))
+    }
+
+    fn captures(&self, haystack: &str) -> Vec<Captures> {
+        // 与 find_matches 相同：最左边的匹配优先，起点相同时保留更长的，
+        // 因此每个匹配的捕获组来自产生它的子匹配器
+        let mut captures: Vec<Captures> = self
+            .matchers
+            .iter()
+            .flat_map(|m| m.captures(haystack))
+            .collect();
+        captures.sort_by_key(|caps| {
+            let (start, end) = caps.span();
+            (start, Reverse(end))
+        });
+        let mut result: Vec<Captures> = Vec::with_capacity(captures.len());
+        for caps in captures {
+            let (start, end) = caps.span();
+            if let Some(last) = result.last() {
+                let (_, last_end) = last.span();
+                if start < last_end || (start == last_end && start == end) {
+                    continue;
+                }
+            }
+            result.push(caps);
+        }
+        result
+    }
+
+    fn find_bytes(&self, haystack: &[u8]) -> Vec<(usize, usize)> {
+        let mut matches: Vec<Match> = self
+            .matchers
+            .iter()
+            .flat_map(|m| m.find_bytes(haystack))
+            .map(|(start, end)| Match::new(start, end, 0, String::new()))
+            .collect();
+        matches.sort_by_key(|m| (m.start, Reverse(m.end)));
+        remove_overlapping(matches)
+            .into_iter()
+            .map(|m| (m.start, m.end))
+            .collect()
+    }
+}
+
+/// 多个固定字符串的匹配器（`-F` 搭配多个 `-e`）
+///
+/// 使用 Aho-Corasick 一次扫描同时查找所有字符串，而不是拼接成一个转义后的大正则。
+/// 匹配语义与按相同顺序组成的交替 `a|b|c` 一致：从最左边开始，位置相同时先出现的模式优先。
+pub struct MultiLiteralMatcher {
+    automaton: AhoCorasick,
+}
+
+impl MultiLiteralMatcher {
+    pub fn new<P: AsRef<str>>(literals: &[P]) -> Result<Self> {
+        let automaton = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostFirst)
+            .build(literals.iter().map(|lit| lit.as_ref()))?;
+        Ok(Self { automaton })
+    }
+}
+
+impl Matcher for MultiLiteralMatcher {
+    fn find_matches(&self, haystack: &str) -> Vec<Match> {
+        // 模式中可能有空字符串（例如 `-f` 文件中的空行）
+        let matches = self
+            .automaton
+            .find_iter(haystack)
+            .map(|mat| {
+                Match::new(
+                    mat.start(),
+                    mat.end(),
+                    0, // line will be filled by Searcher
+                    haystack[mat.range()].to_string(),
+                )
+            })
+            .collect();
+        collapse_empty_matches(haystack, matches)
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        self.automaton.is_match(haystack)
+    }
+
+    fn find_bytes(&self, haystack: &[u8]) -> Vec<(usize, usize)> {
+        self.automaton
+            .find_iter(haystack)
+            .map(|mat| (mat.start(), mat.end()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_orders_by_line_then_start_then_end() {
+        let m = |line, start, end| Match::new(start, end, line, "x".to_string());
+        assert!(m(1, 5, 6) < m(2, 0, 1));
+        assert!(m(1, 0, 9) < m(1, 1, 2));
+        // 起点相同时较短的匹配在前
+        assert!(m(1, 2, 3) < m(1, 2, 5));
+        assert_eq!(m(1, 2, 3).cmp(&m(1, 2, 3)), Ordering::Equal);
+
+        let mut matches = [m(2, 0, 1), m(1, 2, 5), m(1, 2, 3), m(1, 0, 4)];
+        matches.sort();
+        let keys: Vec<(usize, usize, usize)> = matches.iter().map(|m| (m.line, m.start, m.end)).collect();
+        assert_eq!(keys, [(1, 0, 4), (1, 2, 3), (1, 2, 5), (2, 0, 1)]);
+    }
+
+    #[test]
+    fn match_ordering_is_consistent_with_equality() {
+        let a = Match::new(0, 3, 1, "foo".to_string());
+        let b = Match::new(0, 3, 1, "bar".to_string());
+        // 位置相同但内容不同：不相等，排序也不能是 Equal
+        assert_ne!(a, b);
+        assert_eq!(b.cmp(&a), Ordering::Less);
+
+        let mut grouped = a.clone();
+        grouped.groups = vec![(0, 1)];
+        assert_ne!(a, grouped);
+        assert_ne!(a.cmp(&grouped), Ordering::Equal);
+        assert_eq!(a.partial_cmp(&grouped), Some(a.cmp(&grouped)));
+        assert_eq!(a, a.clone());
+    }
+
+    #[test]
+    fn anchored_alternatives() {
+        assert_eq!(
+            extract_anchored_alternatives("^(GET|POST|PUT) "),
+            Some(vec!["GET".to_string(), "POST".to_string(), "PUT".to_string()])
+        );
+        assert_eq!(
+            extract_anchored_alternatives("^(?:GET|POST)"),
+            Some(vec!["GET".to_string(), "POST".to_string()])
+        );
+        for pattern in ["^(GET|POST)|foo", "foo|^(GET|POST)", "^(GET|POST)?x", "^(GET|P.ST)", "^(GET|)"] {
+            assert_eq!(extract_anchored_alternatives(pattern), None, "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn anchored_prefilter_rejects_lines_without_alternative() {
+        let matcher = RegexMatcher::new("^(GET|POST|PUT) ").unwrap();
+        assert!(matcher.anchored_prefixes.is_some());
+        assert!(matcher.is_match("POST /api"));
+        assert!(!matcher.is_match("DELETE /api"));
+        assert!(!matcher.is_match(" GET /api"));
+        assert!(matcher.find_matches("HEAD /GET ").is_empty());
+    }
+
+    fn or_matcher(patterns: &[&str]) -> OrMatcher<RegexMatcher> {
+        OrMatcher::new(patterns.iter().map(|p| RegexMatcher::new(p).unwrap()).collect())
+    }
+
+    #[test]
+    fn or_matcher_finds_leftmost_matches_of_all_patterns() {
+        let matcher = or_matcher(&["b+", "ab"]);
+        let found: Vec<(usize, usize)> = matcher
+            .find_matches("xabbb bb")
+            .iter()
+            .map(|m| (m.start, m.end))
+            .collect();
+        // `ab` 从更左边开始，与之重叠的 `bbb` 被丢弃
+        assert_eq!(found, [(1, 3), (6, 8)]);
+        assert!(matcher.is_match("only b"));
+        assert!(!matcher.is_match("nothing"));
+    }
+
+    #[test]
+    fn or_matcher_captures_come_from_the_matching_pattern() {
+        let matcher = or_matcher(&[r"(\w+)=", r"f(o+)"]);
+        let haystack = "key=val foo";
+        let captures = matcher.captures(haystack);
+        assert_eq!(captures.len(), 2);
+        assert_eq!(captures[0].span(), (0, 4));
+        assert_eq!(captures[0].get(1), Some((0, 3)));
+        assert_eq!(captures[1].span(), (8, 11));
+        assert_eq!(captures[1].get(1), Some((9, 11)));
+
+        let mut replaced = String::new();
+        captures[1].expand(haystack, "<$1>", &mut replaced);
+        assert_eq!(replaced, "<oo>");
+    }
+
+    #[test]
+    fn or_matcher_captures_drop_overlapping_matches() {
+        // 起点相同时保留更长的匹配，与 find_matches 一致
+        let matcher = or_matcher(&["(a)", "(a)(b)"]);
+        let captures = matcher.captures("ab a");
+        let spans: Vec<(usize, usize)> = captures.iter().map(Captures::span).collect();
+        assert_eq!(spans, [(0, 2), (3, 4)]);
+        assert_eq!(captures[0].get(2), Some((1, 2)));
+    }
+
+    #[test]
+    fn multi_line_line_scope_matches_each_line() {
+        let haystack = "bar\nfoo\nfoox\n";
+        let matcher = RegexMatcher::multi_line("foo", MatchScope::Line).unwrap();
+        let found: Vec<(usize, usize)> =
+            matcher.find_matches(haystack).iter().map(|m| (m.start, m.end)).collect();
+        assert_eq!(found, [(4, 7)]);
+        // 多行时 `^` 不只是文本的开头，锚定分支的预过滤不能拒绝这段文本
+        let matcher = RegexMatcher::multi_line("^(foo|baz)$", MatchScope::Any).unwrap();
+        assert!(matcher.is_match(haystack));
+    }
+
+    #[test]
+    fn anchor_only_patterns_match_once_per_line() {
+        let spans = |matcher: &RegexMatcher, haystack: &str| -> Vec<(usize, usize)> {
+            matcher.find_matches(haystack).iter().map(|m| (m.start, m.end)).collect()
+        };
+        let start = RegexMatcher::new("^").unwrap();
+        let end = RegexMatcher::new("$").unwrap();
+        assert_eq!(spans(&start, "foo bar"), [(0, 0)]);
+        assert_eq!(spans(&end, "foo bar"), [(7, 7)]);
+        assert_eq!(spans(&start, ""), [(0, 0)]);
+        // 每行只保留一个空匹配
+        let both = RegexMatcher::new("^|$").unwrap();
+        assert_eq!(spans(&both, "foo"), [(0, 0)]);
+        let multi_line = RegexMatcher::multi_line("$", MatchScope::Any).unwrap();
+        assert_eq!(spans(&multi_line, "ab\ncde\n"), [(2, 2), (6, 6), (7, 7)]);
+        // 同一行有非空匹配时丢弃空匹配
+        let optional = RegexMatcher::new("x*").unwrap();
+        assert_eq!(spans(&optional, "axxb"), [(1, 3)]);
+    }
+
+    #[test]
+    fn multi_literal_matcher_agrees_with_escaped_alternation() {
+        let literals = ["foo", "a.b", "foobar"];
+        let matcher = MultiLiteralMatcher::new(&literals).unwrap();
+        let alternation = RegexMatcher::new(&literals.map(regex::escape).join("|")).unwrap();
+        let spans = |matches: Vec<Match>| -> Vec<(usize, usize)> {
+            matches.iter().map(|m| (m.start, m.end)).collect()
+        };
+        for haystack in ["foobar a.b axb", "xfoo foobarfoo", "a.bfoo", "nothing"] {
+            let expected = spans(alternation.find_matches(haystack));
+            assert_eq!(spans(matcher.find_matches(haystack)), expected, "{}", haystack);
+        }
+        // 位置相同时先给出的模式优先，`.` 只匹配它自己
+        assert_eq!(spans(matcher.find_matches("foobar a.b axb")), [(0, 3), (7, 10)]);
+    }
+
+    #[test]
+    fn patterns_matching_the_empty_string_match_each_line_once() {
+        let spans = |matcher: &RegexMatcher, haystack: &str| -> Vec<(usize, usize)> {
+            matcher.find_matches(haystack).iter().map(|m| (m.start, m.end)).collect()
+        };
+        let empty_group = RegexMatcher::new("(?:)").unwrap();
+        assert_eq!(spans(&empty_group, "xyz"), [(0, 0)]);
+        assert_eq!(spans(&empty_group, ""), [(0, 0)]);
+        // 有非空匹配的行只报告非空匹配，其余的行在行首报告一个空匹配
+        let empty_branch = RegexMatcher::new("a|").unwrap();
+        assert_eq!(spans(&empty_branch, "xaya"), [(1, 2), (3, 4)]);
+        assert_eq!(spans(&empty_branch, "xyz"), [(0, 0)]);
+        assert!(empty_branch.is_match("xyz"));
+        // 与空模式的处理一致
+        assert_eq!(spans(&RegexMatcher::new("").unwrap(), "xyz"), [(0, 0)]);
+    }
+
+    // 预过滤只能跳过不可能匹配的行，结果必须与直接使用正则完全相同
+    #[test]
+    fn prefilters_agree_with_regex() {
+        let patterns = [
+            "^(GET|POST)|foo",
+            "foo|^(GET|POST)",
+            "^(GET|POST|PUT) ",
+            "^(?:GET|POST)+/",
+            "^(GET|POST)*x",
+            "hello",
+            "hel+o",
+            "a|hello",
+            "wor?ld",
+            "z[0-9]",
+            "^fo",
+            "o$",
+        ];
+        let lines = [
+            "",
+            "GET /index",
+            "POST/",
+            "PUT x",
+            "x",
+            "a foo line",
+            "foo",
+            "hello world",
+            "helllo",
+            "wold",
+            "z1 and z2",
+            "  GET",
+        ];
+        for pattern in patterns {
+            let matcher = RegexMatcher::new(pattern).unwrap();
+            let regex = Regex::new(pattern).unwrap();
+            for line in lines {
+                let expected: Vec<(usize, usize)> =
+                    regex.find_iter(line).map(|m| (m.start(), m.end())).collect();
+                let found: Vec<(usize, usize)> =
+                    matcher.find_matches(line).iter().map(|m| (m.start, m.end)).collect();
+                assert_eq!(matcher.is_match(line), regex.is_match(line), "{:?} on {:?}", pattern, line);
+                // 零宽匹配会被合并，只比较非空匹配的情况
+                if expected.iter().all(|(start, end)| start < end) {
+                    assert_eq!(found, expected, "{:?} on {:?}", pattern, line);
+                }
+            }
+        }
+    }
 }
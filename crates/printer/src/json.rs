@@ -0,0 +1,108 @@
+//! `--json` 的事件序列化：每个事件是一行 JSON（JSON Lines），格式与 ripgrep 相同
+//!
+//! 每个有输出的文件依次产生 `begin`、若干 `match` / `context`、`end`，
+//! 所有文件搜索完成后输出一个 `summary`。
+
+use std::time::Duration;
+use searcher::{Line, LineKind};
+
+/// 一个文件（或整次运行）中输出的匹配行数与匹配数
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Counts {
+    pub matched_lines: usize,
+    pub matches: usize,
+}
+
+impl Counts {
+    pub fn add_lines(&mut self, lines: &[Line]) {
+        for line in lines.iter().filter(|line| line.kind == LineKind::Match) {
+            self.matched_lines += 1;
+            self.matches += line.matches.len();
+        }
+    }
+
+    fn to_json(self) -> String {
+        format!(
+            r#"{{"matched_lines":{},"matches":{}}}"#,
+            self.matched_lines, self.matches
+        )
+    }
+}
+
+pub(crate) fn begin(path: &str) -> String {
+    format!(r#"{{"type":"begin","data":{{"path":{{"text":{}}}}}}}"#, string(path))
+}
+
+pub(crate) fn end(path: &str, counts: Counts) -> String {
+    format!(
+        r#"{{"type":"end","data":{{"path":{{"text":{}}},"stats":{}}}}}"#,
+        string(path),
+        counts.to_json()
+    )
+}
+
+/// `searches_with_match` 是有匹配输出的文件数
+pub(crate) fn summary(searches_with_match: usize, counts: Counts, elapsed: Duration) -> String {
+    format!(
+        r#"{{"type":"summary","data":{{"elapsed_total":{{"secs":{},"nanos":{},"human":"{:.6}s"}},"stats":{{"searches_with_match":{},"matched_lines":{},"matches":{}}}}}}}"#,
+        elapsed.as_secs(),
+        elapsed.subsec_nanos(),
+        elapsed.as_secs_f64(),
+        searches_with_match,
+        counts.matched_lines,
+        counts.matches
+    )
+}
+
+/// 匹配行或上下文行；`submatches` 的位置是行内的字节偏移，另附字符偏移
+pub(crate) fn line(path: &str, line: &Line) -> String {
+    let kind = match line.kind {
+        LineKind::Match => "match",
+        LineKind::Context => "context",
+    };
+    let submatches: Vec<String> = line
+        .matches
+        .iter()
+        .filter_map(|m| {
+            let text = line.content.get(m.start..m.end)?;
+            let char_start = line.content[..m.start].chars().count();
+            let char_end = char_start + text.chars().count();
+            Some(format!(
+                r#"{{"match":{{"text":{}}},"start":{},"end":{},"char_start":{},"char_end":{}}}"#,
+                string(text),
+                m.start,
+                m.end,
+                char_start,
+                char_end
+            ))
+        })
+        .collect();
+    format!(
+        r#"{{"type":"{}","data":{{"path":{{"text":{}}},"lines":{{"text":{}}},"line_number":{},"absolute_offset":{},"submatches":[{}]}}}}"#,
+        kind,
+        string(path),
+        string(&line.content),
+        line.number,
+        line.offset,
+        submatches.join(",")
+    )
+}
+
+// 转成带引号的 JSON 字符串
+fn string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -1,28 +1,892 @@
-use std::io::{self, Write};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io::{self, BufWriter, Write};
 use std::path::Path;
+use std::time::Instant;
 use matcher::Match;
+use searcher::{Line, LineKind};
+
+mod json;
+
+// --hex 每行输出的字节数
+const HEX_ROW_LEN: usize = 16;
+
+// 颜色输出用的 ANSI 转义序列：路径为品红色，行号为绿色，匹配为加粗的红色，
+// 匹配中的捕获组再加下划线
+const COLOR_PATH: &str = "\x1b[35m";
+const COLOR_LINE_NUMBER: &str = "\x1b[32m";
+const COLOR_MATCH: &str = "\x1b[1;31m";
+const COLOR_GROUP: &str = "\x1b[1;4;31m";
+const COLOR_RESET: &str = "\x1b[0m";
+
+/// 输出配置
+#[derive(Debug, Clone)]
+pub struct PrinterConfig {
+    /// 是否输出了上下文行；开启后不连续的行组之间用 `--` 分隔
+    pub context: bool,
+    /// 全局去重：整个运行期间内容相同的行只打印第一次，不论所在的文件和行号
+    /// （只比较内容，不含路径、行号等前缀；`--` 分隔符除外）
+    pub dedup_lines: bool,
+    /// 分组输出：文件路径单独占一行，其下是 `行号:内容`，文件之间空一行
+    pub heading: bool,
+    /// 只输出匹配到的文本，每个匹配单独一行（行号相同）；heading 模式下同一行的
+    /// 后续匹配不重复行号，用空格对齐
+    pub only_matching: bool,
+    /// 匹配行中路径、行号、内容之间的分隔符
+    pub field_match_separator: String,
+    /// 上下文行中路径、行号、内容之间的分隔符
+    pub field_context_separator: String,
+    /// 每输出一行就刷新（实时查看管道输出）；关闭时按块缓冲以提高吞吐
+    pub line_buffered: bool,
+    /// 是否在每行（或计数）前输出文件路径；关闭时 heading 模式也不输出路径行
+    pub with_filename: bool,
+    /// 超过该字符数的行被截断，省略的部分用 `...` 表示
+    pub max_columns: Option<usize>,
+    /// 截断时以行中第一个匹配为中心取窗口（而不是保留行首），保证匹配可见
+    pub max_columns_preview: bool,
+    /// 每条输出以 NUL 而不是换行结束（`--null-data`），记录中可能包含换行
+    pub null_data: bool,
+    /// 输出 JSON Lines 事件：每个匹配行、上下文行一个对象，每个文件前后有 `begin` / `end`，
+    /// 最后是 `summary`（格式见 `json` 模块）
+    pub json: bool,
+    /// 路径之后用 NUL 代替字段分隔符（`--null`），输出可以交给 `xargs -0` 等工具。
+    /// heading 模式下改为整体以 NUL 分帧：路径标题、每个输出行（包括 `--`）都是以 NUL
+    /// 结束的记录，每个文件的分组以一个空记录结束，即 `路径\0(行\0)*\0`
+    pub null: bool,
+    /// 输出路径时按 shell 规则加引号（`--quote`）：含有 `:`、空白、换行等字符的路径
+    /// 不会破坏 `路径:行号:内容` 格式；含控制字符时使用 `$'...'` 转义。
+    /// 关闭时路径原样输出，需要可靠解析时可以改用 `--null-data` 或 `--json`
+    pub quote_paths: bool,
+    /// 用 ANSI 颜色输出：路径为品红色，行号为绿色，匹配为加粗的红色，
+    /// 匹配中的捕获组（`Match::groups`）额外加下划线。被 `max_columns` 截断的行内容不着色
+    pub color: bool,
+    /// 在行号之后输出字节偏移（`--byte-offset`）：行首在文件中的绝对偏移，
+    /// `-o` 模式下是每个匹配自身的偏移
+    pub byte_offset: bool,
+    /// 行号右对齐到当前文件中最大行号的宽度（`--line-number-width auto`），
+    /// 例如 `  12:foo` / ` 123:bar`
+    pub align_line_numbers: bool,
+    /// 每个匹配单独输出一次（`--vimgrep`），格式为 `路径:行号:列:内容`，
+    /// 列是匹配在行中从 1 开始的字节位置；没有匹配的命中行（`-v`）列为 1
+    pub vimgrep: bool,
+}
+
+impl Default for PrinterConfig {
+    fn default() -> Self {
+        PrinterConfig {
+            context: false,
+            dedup_lines: false,
+            heading: false,
+            only_matching: false,
+            field_match_separator: ":".to_string(),
+            field_context_separator: "-".to_string(),
+            line_buffered: false,
+            with_filename: true,
+            max_columns: None,
+            max_columns_preview: false,
+            null_data: false,
+            json: false,
+            null: false,
+            quote_paths: false,
+            color: false,
+            byte_offset: false,
+            align_line_numbers: false,
+            vimgrep: false,
+        }
+    }
+}
 
 pub struct Printer {
-    // 移除 output 字段，因为 print_match 中直接使用 stdout()
-    // 这样可以避免 Box<dyn Write> 的 Send 问题
+    // 输出目标需要是 Send，打印器会在 rayon 工作线程之间通过 Mutex 共享
+    out: BufWriter<Box<dyn Write + Send>>,
+    config: PrinterConfig,
+    // 是否已经输出过任何行（用于在文件之间插入 `--`）
+    printed_any: bool,
+    // 已经输出过的行（仅在 dedup_lines 开启时记录）
+    seen: HashSet<String>,
+    // 当前文件中最后输出的行号（判断是否需要 `--`）
+    last_number: Option<usize>,
+    // NUL 分帧的 heading 模式下，当前文件的分组还没有输出结束记录
+    group_open: bool,
+    // 当前文件的行号宽度（对齐行号时使用）
+    number_width: usize,
+    // `--json`：已经输出 `begin`、还没有输出 `end` 的文件中的计数
+    json_file: Option<json::Counts>,
+    // `--json`：有输出的文件数以及所有文件的计数，用于最后的 `summary`
+    json_files: usize,
+    json_total: json::Counts,
+    started: Instant,
+}
+
+impl Default for Printer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Printer {
     pub fn new() -> Self {
-        Printer {}
+        Self::with_config(PrinterConfig::default())
+    }
+
+    pub fn with_config(config: PrinterConfig) -> Self {
+        Self::with_writer(config, Box::new(io::stdout()))
+    }
+
+    /// 输出到任意目标（默认是标准输出）
+    pub fn with_writer(config: PrinterConfig, writer: Box<dyn Write + Send>) -> Self {
+        Printer {
+            out: BufWriter::new(writer),
+            config,
+            printed_any: false,
+            seen: HashSet::new(),
+            last_number: None,
+            group_open: false,
+            number_width: 0,
+            json_file: None,
+            json_files: 0,
+            json_total: json::Counts::default(),
+            started: Instant::now(),
+        }
+    }
+
+    pub fn print_match(&mut self, path: &Path, m: &Match) -> io::Result<()> {
+        let line = format!("{}{}:{}", self.path_prefix(path, ":"), m.line, m.content);
+        self.write_line(&line)
     }
 
-    pub fn print_match(&self, path: &Path, m: &Match) -> io::Result<()> {
-        let stdout = io::stdout();
-        let mut handle = stdout.lock();
+    // 写出一行；行缓冲模式下立即刷新
+    fn write_line(&mut self, text: &str) -> io::Result<()> {
+        let terminator = if self.config.null_data || self.null_framed() { '\0' } else { '\n' };
+        write!(self.out, "{}{}", text, terminator)?;
+        if self.config.line_buffered {
+            self.out.flush()?;
+        }
+        Ok(())
+    }
+
+    /// 输出一个文件的搜索结果：匹配行用 `:` 分隔，上下文行用 `-` 分隔
+    pub fn print_lines(&mut self, path: &Path, lines: &[Line]) -> io::Result<()> {
+        if lines.is_empty() {
+            return Ok(());
+        }
+        if self.config.json {
+            return self.print_json_lines(path, lines);
+        }
+
+        if self.heading() {
+            if self.null_framed() {
+                self.close_group()?;
+                self.group_open = true;
+            } else if self.printed_any {
+                self.write_line("")?;
+            }
+            let path_text = self.path_text(path);
+            self.write_line(&path_text)?;
+            self.printed_any = true;
+        }
+        self.last_number = None;
+        self.number_width = 0;
+        self.write_lines(path, lines)
+    }
+
+    /// 继续输出同一个文件的后续行（流式搜索时分批调用，第一批用 `print_lines`）：
+    /// 不再输出路径标题，与上一批之间只在行号不连续时插入 `--`
+    pub fn print_more_lines(&mut self, path: &Path, lines: &[Line]) -> io::Result<()> {
+        if self.config.json {
+            return self.print_json_lines(path, lines);
+        }
+        self.write_lines(path, lines)
+    }
+
+    // 不输出路径时没有路径行可分组，heading 退化为普通的逐行输出
+    fn heading(&self) -> bool {
+        self.config.heading && self.config.with_filename
+    }
+
+    // heading 模式下的 NUL 分帧（`--null`）
+    fn null_framed(&self) -> bool {
+        self.config.null && self.heading()
+    }
+
+    // 输出当前文件分组的结束记录（空记录）
+    fn close_group(&mut self) -> io::Result<()> {
+        if self.group_open {
+            self.group_open = false;
+            self.write_line("")?;
+        }
+        Ok(())
+    }
+
+    fn write_lines(&mut self, path: &Path, lines: &[Line]) -> io::Result<()> {
+        let heading = self.heading();
+        // 流式输出时宽度只能随批次增长，已经输出的行不会重新对齐
+        if self.config.align_line_numbers {
+            let widest = lines.iter().map(|line| line.number).max().unwrap_or(0);
+            self.number_width = self.number_width.max(widest.to_string().len());
+        }
+        for line in lines {
+            let separator = match line.kind {
+                LineKind::Match => self.config.field_match_separator.clone(),
+                LineKind::Context => self.config.field_context_separator.clone(),
+            };
+            // -o 模式下每条文本就是匹配本身，不需要再定位
+            let focus = match self.config.only_matching {
+                true => None,
+                false => line.matches.first().map(|m| (m.start, m.end)),
+            };
+            // 这一行是否已经输出过文本（-o 时一行可能输出多条）
+            let mut emitted = false;
+            // heading 模式下 -o 同一行的多个匹配各占一行，但只在第一个前面写行号，
+            // 之后的用空格对齐；--vimgrep 每条都需要完整的位置
+            let grouped = heading && self.config.only_matching && !self.config.vimgrep;
+            for (raw, base, column) in self.line_texts(line) {
+                // 去重只比较匹配的原始内容（截断和着色之前）：路径、行号和偏移每行都不同，
+                // 不能作为比较的一部分
+                if self.config.dedup_lines && !self.seen.insert(raw.to_string()) {
+                    continue;
+                }
+                // 被去重跳过的行不算输出过，之后的行与它不连续时仍然插入 `--`
+                if !emitted {
+                    self.write_context_break(line.number, heading)?;
+                    self.last_number = Some(line.number);
+                }
+                let text = match self.fit_columns(raw, focus) {
+                    Cow::Borrowed(text) if self.config.color => Cow::Owned(highlight(text, base, &line.matches)),
+                    text => text,
+                };
+                // 行号（以及 --vimgrep 的列号）连同之后的分隔符
+                let number = format!("{:>width$}", line.number, width = self.number_width);
+                let mut prefix = match grouped && emitted {
+                    true => " ".repeat(number.len() + separator.chars().count()),
+                    false => format!("{}{}", self.colored(number, COLOR_LINE_NUMBER), separator),
+                };
+                if self.config.vimgrep {
+                    prefix = format!("{}{}{}", prefix, column + 1, separator);
+                }
+                let body = match self.config.byte_offset {
+                    true => format!("{}{}{}{}", prefix, line.offset + base, separator, text),
+                    false => format!("{}{}", prefix, text),
+                };
+                let full = format!("{}{}", self.path_prefix(path, &separator), body);
+                if heading || !self.config.with_filename {
+                    self.write_line(&body)?;
+                } else {
+                    self.write_line(&full)?;
+                }
+                self.printed_any = true;
+                emitted = true;
+            }
+        }
+        Ok(())
+    }
 
-        writeln!(
-            handle,
-            "{}:{}:{}",
-            path.display(),
-            m.line,
-            m.content
-        )?;
+    // 上下文模式下，与上一条输出的行不连续时先输出 `--`
+    fn write_context_break(&mut self, number: usize, heading: bool) -> io::Result<()> {
+        if !self.config.context {
+            return Ok(());
+        }
+        let contiguous = self.last_number.is_some_and(|n| number == n + 1);
+        // heading 模式下文件之间已经用空行分隔，`--` 只出现在文件内部
+        let started = if heading {
+            self.last_number.is_some()
+        } else {
+            self.printed_any
+        };
+        if started && !contiguous {
+            self.write_line("--")?;
+        }
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// 计数模式：输出 `路径:数量`（不输出路径时只有数量）
+    pub fn print_count(&mut self, path: &Path, count: usize) -> io::Result<()> {
+        let line = if self.config.with_filename {
+            format!("{}{}", self.path_prefix(path, &self.config.field_match_separator), count)
+        } else {
+            count.to_string()
+        };
+        self.write_line(&line)?;
+        self.printed_any = true;
+        Ok(())
+    }
+
+    /// 只输出文件路径（`-l`）；`--null` 时路径之后是 NUL 而不是换行，便于交给 `xargs -0`
+    pub fn print_path(&mut self, path: &Path) -> io::Result<()> {
+        let terminator = if self.config.null { '\0' } else { '\n' };
+        write!(self.out, "{}{}", self.path_text(path), terminator)?;
+        if self.config.line_buffered {
+            self.out.flush()?;
+        }
+        self.printed_any = true;
+        Ok(())
+    }
+
+    /// `--hex`：把匹配到的字节输出为十六进制转储，每行最多 16 个字节：
+    /// `路径:偏移: 十六进制字节  ASCII`。偏移是该行第一个字节在数据中的绝对位置（十六进制），
+    /// 不可打印的字节在 ASCII 部分显示为 `.`
+    pub fn print_hex(&mut self, path: &Path, data: &[u8], matches: &[(usize, usize)]) -> io::Result<()> {
+        for &(start, end) in matches {
+            let Some(bytes) = data.get(start..end) else {
+                continue;
+            };
+            for (row, chunk) in bytes.chunks(HEX_ROW_LEN).enumerate() {
+                let offset = start + row * HEX_ROW_LEN;
+                let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+                let ascii: String = chunk
+                    .iter()
+                    .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+                    .collect();
+                let dump = format!("{:08x}: {:<width$}  {}", offset, hex.join(" "), ascii, width = HEX_ROW_LEN * 3 - 1);
+                let line = if self.config.with_filename {
+                    format!("{}{}", self.path_prefix(path, &self.config.field_match_separator), dump)
+                } else {
+                    dump
+                };
+                self.write_line(&line)?;
+                self.printed_any = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// 输出一行汇总信息（不带路径），在所有文件的结果之后调用
+    pub fn print_summary(&mut self, text: &str) -> io::Result<()> {
+        self.write_line(text)?;
+        self.printed_any = true;
+        Ok(())
+    }
+
+    /// 立即写出缓冲区中的内容（流式输出时每批结果之后调用）
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    /// 一个文件的结果已经全部输出（`print_lines` / `print_more_lines` 之后调用）。
+    /// `--json` 时输出该文件的 `end` 事件，其他格式不需要
+    pub fn end_file(&mut self, path: &Path) -> io::Result<()> {
+        let Some(counts) = self.json_file.take() else {
+            return Ok(());
+        };
+        self.json_files += 1;
+        self.json_total.matched_lines += counts.matched_lines;
+        self.json_total.matches += counts.matches;
+        self.write_line(&json::end(&path.display().to_string(), counts))
+    }
+
+    /// 结束输出：在所有文件搜索完成后调用一次，结束最后一个分组并把缓冲区中剩余的内容写出。
+    /// `--json` 时最后输出 `summary` 事件
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.close_group()?;
+        if self.config.json {
+            let summary = json::summary(self.json_files, self.json_total, self.started.elapsed());
+            self.write_line(&summary)?;
+        }
+        self.out.flush()
+    }
+
+    // 输出中的路径文本（`quote_paths` 时按需加引号，颜色输出时着色）
+    fn path_text(&self, path: &Path) -> String {
+        let text = path.display().to_string();
+        let text = if self.config.quote_paths {
+            shell_quote(&text)
+        } else {
+            text
+        };
+        self.colored(text, COLOR_PATH)
+    }
+
+    // 颜色输出时用 `color` 包住整段文本
+    fn colored(&self, text: String, color: &str) -> String {
+        match self.config.color {
+            true => format!("{}{}{}", color, text, COLOR_RESET),
+            false => text,
+        }
+    }
+
+    // 行首的路径及其后的分隔符（`--null` 时为 NUL）
+    fn path_prefix(&self, path: &Path, separator: &str) -> String {
+        let separator = if self.config.null { "\0" } else { separator };
+        format!("{}{}", self.path_text(path), separator)
+    }
+
+    // 一行实际要输出的文本、它在行中的起始字节位置，以及对应匹配的起始字节位置（列）：
+    // -o 模式下匹配行拆成每个（非空）匹配各一条；vimgrep 模式下每个匹配各输出一次整行；
+    // 其余情况输出整行，列是第一个匹配的位置
+    fn line_texts<'l>(&self, line: &'l Line) -> Vec<(&'l str, usize, usize)> {
+        if self.config.only_matching && line.kind == LineKind::Match {
+            line.matches
+                .iter()
+                .filter(|m| m.start < m.end)
+                .filter_map(|m| Some((line.content.get(m.start..m.end)?, m.start, m.start)))
+                .collect()
+        } else if self.config.vimgrep && line.matches.len() > 1 {
+            line.matches
+                .iter()
+                .map(|m| (line.content.as_str(), 0, m.start))
+                .collect()
+        } else {
+            let column = line.matches.first().map_or(0, |m| m.start);
+            vec![(line.content.as_str(), 0, column)]
+        }
+    }
+
+    // --max-columns：超长的行只保留 max_columns 个字符，省略的部分用 `...` 表示。
+    // preview 模式下窗口以第一个匹配（字节范围 focus）为中心，匹配两侧按需省略
+    fn fit_columns<'t>(&self, text: &'t str, focus: Option<(usize, usize)>) -> Cow<'t, str> {
+        let Some(max) = self.config.max_columns else {
+            return Cow::Borrowed(text);
+        };
+        let total = text.chars().count();
+        if total <= max {
+            return Cow::Borrowed(text);
+        }
+
+        let start = match focus {
+            Some((start, end)) if self.config.max_columns_preview && end <= text.len() => {
+                let match_start = text[..start].chars().count();
+                let match_len = text[start..end].chars().count();
+                let slack = max.saturating_sub(match_len);
+                match_start.saturating_sub(slack / 2).min(total - max)
+            }
+            _ => 0,
+        };
+        let window: String = text.chars().skip(start).take(max).collect();
+        let before = if start > 0 { "..." } else { "" };
+        let after = if start + max < total { "..." } else { "" };
+        Cow::Owned(format!("{}{}{}", before, window, after))
+    }
+
+    // 每行输出一个 JSON 事件；文件的第一批结果之前输出 `begin`
+    fn print_json_lines(&mut self, path: &Path, lines: &[Line]) -> io::Result<()> {
+        let path_text = path.display().to_string();
+        if self.json_file.is_none() {
+            self.write_line(&json::begin(&path_text))?;
+            self.json_file = Some(json::Counts::default());
+        }
+        for line in lines {
+            self.write_line(&json::line(&path_text, line))?;
+            self.printed_any = true;
+        }
+        if let Some(counts) = &mut self.json_file {
+            counts.add_lines(lines);
+        }
+        Ok(())
+    }
+
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Style {
+    Plain,
+    Match,
+    Group,
+}
+
+// 给 `text`（位于行中 `base` 处）中落在匹配和捕获组内的部分加上颜色。
+// 先算出每个字节的样式再输出，样式变化时先复位再切换，嵌套的捕获组因此
+// 不会产生未闭合或交错的转义序列
+fn highlight(text: &str, base: usize, matches: &[Match]) -> String {
+    let end = base + text.len();
+    let mut styles = vec![Style::Plain; text.len()];
+    let mut paint = |start: usize, stop: usize, style: Style| {
+        for pos in start.max(base)..stop.min(end) {
+            styles[pos - base] = style;
+        }
+    };
+    for m in matches {
+        paint(m.start, m.end, Style::Match);
+        for &(start, stop) in &m.groups {
+            paint(start, stop, Style::Group);
+        }
+    }
+
+    let mut out = String::with_capacity(text.len() + 16);
+    let mut current = Style::Plain;
+    for (i, c) in text.char_indices() {
+        let style = styles[i];
+        if style != current {
+            if current != Style::Plain {
+                out.push_str(COLOR_RESET);
+            }
+            match style {
+                Style::Plain => {}
+                Style::Match => out.push_str(COLOR_MATCH),
+                Style::Group => out.push_str(COLOR_GROUP),
+            }
+            current = style;
+        }
+        out.push(c);
+    }
+    if current != Style::Plain {
+        out.push_str(COLOR_RESET);
+    }
+    out
+}
+
+// 按 shell 规则给路径加引号：只含安全字符的路径原样返回；含控制字符（换行等）时
+// 用 `$'...'` 并转义，否则用单引号（内部的 `'` 写成 `'\''`）
+fn shell_quote(text: &str) -> String {
+    let safe = |c: char| c.is_alphanumeric() || "/._-+,@%=".contains(c);
+    if !text.is_empty() && text.chars().all(safe) {
+        return text.to_string();
+    }
+    if text.chars().any(char::is_control) {
+        let mut out = String::from("$'");
+        for c in text.chars() {
+            match c {
+                '\n' => out.push_str("\\n"),
+                '\t' => out.push_str("\\t"),
+                '\r' => out.push_str("\\r"),
+                '\\' => out.push_str("\\\\"),
+                '\'' => out.push_str("\\'"),
+                c if c.is_control() => out.push_str(&format!("\\x{:02x}", c as u32)),
+                c => out.push(c),
+            }
+        }
+        out.push('\'');
+        return out;
+    }
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    // 测试用的输出目标：打印器被丢弃后仍然可以读取写入的内容
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    fn printer(config: PrinterConfig) -> (Printer, SharedBuffer) {
+        let buffer = SharedBuffer::default();
+        (Printer::with_writer(config, Box::new(buffer.clone())), buffer)
+    }
+
+    fn matched(number: usize, content: &str, needle: &str) -> Line {
+        let matches = content
+            .match_indices(needle)
+            .map(|(start, text)| Match::new(start, start + text.len(), number, text.to_string()))
+            .collect();
+        Line {
+            number,
+            offset: 0,
+            content: content.to_string(),
+            kind: LineKind::Match,
+            matches,
+        }
+    }
+
+    #[test]
+    fn dedup_lines_compares_content_across_files() {
+        let (mut printer, out) = printer(PrinterConfig {
+            dedup_lines: true,
+            ..PrinterConfig::default()
+        });
+        printer
+            .print_lines(Path::new("a.txt"), &[matched(1, "use foo;", "foo"), matched(7, "foo()", "foo")])
+            .unwrap();
+        printer
+            .print_lines(Path::new("b.txt"), &[matched(3, "use foo;", "foo"), matched(4, "foo(1)", "foo")])
+            .unwrap();
+        printer.finish().unwrap();
+        assert_eq!(out.text(), "a.txt:1:use foo;\na.txt:7:foo()\nb.txt:4:foo(1)\n");
+    }
+
+    // 每个事件的 `type` 字段
+    fn event_types(text: &str) -> Vec<&str> {
+        text.lines()
+            .map(|line| {
+                let rest = line.strip_prefix(r#"{"type":""#).unwrap();
+                &rest[..rest.find('"').unwrap()]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn json_events_are_framed_per_file() {
+        let (mut printer, out) = printer(PrinterConfig {
+            json: true,
+            ..PrinterConfig::default()
+        });
+        let context = Line {
+            number: 2,
+            offset: 8,
+            content: "after".to_string(),
+            kind: LineKind::Context,
+            matches: Vec::new(),
+        };
+        // 第一个文件分两批输出（流式搜索），begin 只出现一次
+        printer.print_lines(Path::new("a.txt"), &[matched(1, "foo foo", "foo")]).unwrap();
+        printer.print_more_lines(Path::new("a.txt"), &[context]).unwrap();
+        printer.end_file(Path::new("a.txt")).unwrap();
+        // 没有输出的文件不产生事件
+        printer.end_file(Path::new("empty.txt")).unwrap();
+        printer.print_lines(Path::new("b.txt"), &[matched(4, "foo", "foo")]).unwrap();
+        printer.end_file(Path::new("b.txt")).unwrap();
+        printer.finish().unwrap();
+
+        let text = out.text();
+        assert_eq!(
+            event_types(&text),
+            ["begin", "match", "context", "end", "begin", "match", "end", "summary"]
+        );
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], r#"{"type":"begin","data":{"path":{"text":"a.txt"}}}"#);
+        assert_eq!(
+            lines[3],
+            r#"{"type":"end","data":{"path":{"text":"a.txt"},"stats":{"matched_lines":1,"matches":2}}}"#
+        );
+        assert!(lines[7].contains(r#""stats":{"searches_with_match":2,"matched_lines":2,"matches":3}"#));
+    }
+
+    #[test]
+    fn without_dedup_every_line_is_printed() {
+        let (mut printer, out) = printer(PrinterConfig::default());
+        printer.print_lines(Path::new("a.txt"), &[matched(1, "foo", "foo")]).unwrap();
+        printer.print_lines(Path::new("b.txt"), &[matched(1, "foo", "foo")]).unwrap();
+        printer.finish().unwrap();
+        assert_eq!(out.text(), "a.txt:1:foo\nb.txt:1:foo\n");
+    }
+
+    fn context(number: usize, content: &str) -> Line {
+        Line {
+            number,
+            offset: 0,
+            content: content.to_string(),
+            kind: LineKind::Context,
+            matches: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn dedup_ignores_coloring() {
+        let (mut printer, out) = printer(PrinterConfig {
+            dedup_lines: true,
+            color: true,
+            ..PrinterConfig::default()
+        });
+        // 内容相同的两行高亮的位置不同（例如来自不同的模式）
+        printer
+            .print_lines(Path::new("a.txt"), &[matched(1, "foo bar", "foo"), matched(2, "foo bar", "bar")])
+            .unwrap();
+        printer.finish().unwrap();
+        assert_eq!(out.text().lines().count(), 1);
+    }
+
+    #[test]
+    fn dedup_compares_untruncated_content() {
+        // 截断后的文本相同，但行内容不同
+        let (mut printer, out) = printer(PrinterConfig {
+            dedup_lines: true,
+            max_columns: Some(3),
+            ..PrinterConfig::default()
+        });
+        printer
+            .print_lines(Path::new("a.txt"), &[matched(1, "foo bar", "foo"), matched(2, "foo baz", "foo")])
+            .unwrap();
+        printer.finish().unwrap();
+        assert_eq!(out.text(), "a.txt:1:foo...\na.txt:2:foo...\n");
+    }
+
+    #[test]
+    fn dedup_keeps_context_separators() {
+        let (mut printer, out) = printer(PrinterConfig {
+            dedup_lines: true,
+            context: true,
+            ..PrinterConfig::default()
+        });
+        // 第 2 行与第 1 行重复被跳过，第 3 行与第 1 行不连续
+        let lines = [matched(1, "foo", "foo"), matched(2, "foo", "foo"), context(3, "bar")];
+        printer.print_lines(Path::new("a.txt"), &lines).unwrap();
+        printer.finish().unwrap();
+        assert_eq!(out.text(), "a.txt:1:foo\n--\na.txt-3-bar\n");
+    }
+
+    #[test]
+    fn only_matching_groups_matches_of_a_line_in_heading_mode() {
+        let (mut printer, out) = printer(PrinterConfig {
+            heading: true,
+            only_matching: true,
+            context: true,
+            ..PrinterConfig::default()
+        });
+        let lines = [matched(3, "foo x foo", "foo"), context(4, "bar"), matched(10, "foo", "foo")];
+        printer.print_lines(Path::new("a.txt"), &lines).unwrap();
+        printer.print_lines(Path::new("b.txt"), &[matched(1, "foo foo", "foo")]).unwrap();
+        printer.finish().unwrap();
+        assert_eq!(out.text(), "a.txt\n3:foo\n  foo\n4-bar\n--\n10:foo\n\nb.txt\n1:foo\n  foo\n");
+    }
+
+    #[test]
+    fn only_matching_without_heading_repeats_the_prefix() {
+        let (mut printer, out) = printer(PrinterConfig {
+            only_matching: true,
+            byte_offset: true,
+            ..PrinterConfig::default()
+        });
+        printer.print_lines(Path::new("a.txt"), &[matched(3, "foo x foo", "foo")]).unwrap();
+        printer.finish().unwrap();
+        assert_eq!(out.text(), "a.txt:3:0:foo\na.txt:3:6:foo\n");
+    }
+
+    // 记录被刷新了多少次的输出目标
+    #[derive(Clone, Default)]
+    struct FlushCounter(Arc<Mutex<usize>>);
+
+    impl Write for FlushCounter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            *self.0.lock().unwrap() += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn line_buffered_flushes_after_every_line() {
+        let lines = [matched(1, "foo", "foo"), matched(2, "foo foo", "foo"), matched(5, "foo", "foo")];
+        // 三个匹配行和一个计数行
+        for (line_buffered, flushes) in [(true, 4), (false, 0)] {
+            let counter = FlushCounter::default();
+            let config = PrinterConfig {
+                line_buffered,
+                ..PrinterConfig::default()
+            };
+            let mut printer = Printer::with_writer(config, Box::new(counter.clone()));
+            printer.print_lines(Path::new("a.txt"), &lines).unwrap();
+            printer.print_count(Path::new("a.txt"), 3).unwrap();
+            assert_eq!(*counter.0.lock().unwrap(), flushes, "line_buffered: {}", line_buffered);
+            printer.finish().unwrap();
+            assert_eq!(*counter.0.lock().unwrap(), flushes + 1, "line_buffered: {}", line_buffered);
+        }
+    }
+
+    #[test]
+    fn buffered_output_is_written_by_finish() {
+        let (mut printer, out) = printer(PrinterConfig {
+            heading: true,
+            null: true,
+            ..PrinterConfig::default()
+        });
+        printer.print_lines(Path::new("a.txt"), &[matched(1, "foo", "foo")]).unwrap();
+        assert_eq!(out.text(), "");
+        // 最后一个分组的结束记录也由 finish 输出
+        printer.finish().unwrap();
+        assert_eq!(out.text(), "a.txt\x001:foo\x00\x00");
+    }
+
+    #[test]
+    fn json_summary_is_written_by_finish() {
+        let (mut printer, out) = printer(PrinterConfig {
+            json: true,
+            ..PrinterConfig::default()
+        });
+        printer.print_lines(Path::new("a.txt"), &[matched(1, "foo", "foo")]).unwrap();
+        printer.end_file(Path::new("a.txt")).unwrap();
+        assert_eq!(out.text(), "");
+        printer.finish().unwrap();
+        assert_eq!(event_types(&out.text()), ["begin", "match", "end", "summary"]);
+    }
+
+    #[test]
+    fn max_columns_preview_keeps_the_match_visible() {
+        let near_end = format!("{} needle {}", "x".repeat(100), "y".repeat(10));
+        let middle = format!("{}needle{}", "a".repeat(50), "b".repeat(50));
+        let lines = [matched(1, &near_end, "needle"), matched(2, &middle, "needle")];
+        let (mut printer, out) = printer(PrinterConfig {
+            max_columns: Some(30),
+            max_columns_preview: true,
+            ..PrinterConfig::default()
+        });
+        printer.print_lines(Path::new("a.txt"), &lines).unwrap();
+        printer.finish().unwrap();
+        // 匹配在行尾附近时窗口靠右，只在左侧省略；在中间时两侧都省略
+        let expected = [
+            format!("a.txt:1:...{} needle {}", "x".repeat(12), "y".repeat(10)),
+            format!("a.txt:2:...{}needle{}...", "a".repeat(12), "b".repeat(12)),
+        ];
+        assert_eq!(out.text(), expected.join("\n") + "\n");
+    }
+
+    #[test]
+    fn max_columns_without_preview_keeps_the_line_start() {
+        let near_end = format!("{} needle", "x".repeat(100));
+        let (mut printer, out) = printer(PrinterConfig {
+            max_columns: Some(30),
+            ..PrinterConfig::default()
+        });
+        printer.print_lines(Path::new("a.txt"), &[matched(1, &near_end, "needle")]).unwrap();
+        printer.finish().unwrap();
+        assert_eq!(out.text(), format!("a.txt:1:{}...\n", "x".repeat(30)));
+    }
+
+    #[test]
+    fn hex_dump_of_matched_bytes() {
+        let (mut printer, out) = printer(PrinterConfig::default());
+        let mut data = b"\x00\x01ELF".to_vec();
+        data.extend(0u8..20);
+        // 超过 16 个字节的匹配分成多行，每行的偏移都是绝对位置
+        printer.print_hex(Path::new("a.bin"), &data, &[(2, 5), (5, 25)]).unwrap();
+        printer.finish().unwrap();
+        let expected = [
+            format!("a.bin:00000002: {:<47}  ELF", "45 4c 46"),
+            "a.bin:00000005: 00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f  ................".to_string(),
+            format!("a.bin:00000015: {:<47}  ....", "10 11 12 13"),
+        ];
+        assert_eq!(out.text(), expected.join("\n") + "\n");
+    }
+
+    #[test]
+    fn capture_groups_are_layered_inside_the_match() {
+        // 模式 `k(e+)y` 在 `a keeey b` 中：匹配 2..7，组 1 为 3..6
+        let mut m = Match::new(2, 7, 1, "keeey".to_string());
+        m.groups = vec![(3, 6)];
+        let highlighted = highlight("a keeey b", 0, &[m.clone()]);
+        let (mat, group, reset) = (COLOR_MATCH, COLOR_GROUP, COLOR_RESET);
+        assert_eq!(highlighted, format!("a {mat}k{reset}{group}eee{reset}{mat}y{reset} b"));
+        // 只输出行的一部分（`-o`）时同样按绝对位置着色，转义序列总是成对出现
+        let part = highlight("eeey", 3, &[m]);
+        assert_eq!(part, format!("{group}eee{reset}{mat}y{reset}"));
+        for text in [&highlighted, &part] {
+            assert_eq!(text.matches("\x1b[").count(), 2 * text.matches(COLOR_RESET).count());
+        }
+    }
+
+    #[test]
+    fn null_framing_in_heading_mode() {
+        let (mut printer, out) = printer(PrinterConfig {
+            heading: true,
+            null: true,
+            context: true,
+            ..PrinterConfig::default()
+        });
+        let first = [matched(1, "foo", "foo"), context(2, "bar"), matched(5, "foo", "foo")];
+        printer.print_lines(Path::new("a.txt"), &first).unwrap();
+        printer.print_lines(Path::new("b.txt"), &[matched(3, "x foo", "foo")]).unwrap();
+        printer.finish().unwrap();
+        // 标题、每一行和 `--` 各是一条以 NUL 结尾的记录，每个文件的分组以一条空记录结束
+        let records = ["a.txt", "1:foo", "2-bar", "--", "5:foo", "", "b.txt", "3:x foo", ""];
+        assert_eq!(out.text(), records.map(|record| format!("{}\0", record)).concat());
+    }
+}
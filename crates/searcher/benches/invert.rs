@@ -0,0 +1,76 @@
+//! 反向匹配（`-v`）的吞吐量：搜索器逐行调用 `is_match`，找到第一个匹配即可停止；
+//! 对照组把 `is_match` 实现为 `find_matches` 的结果是否为空（改动之前每行的做法）。
+//!
+//! 运行：`cargo bench -p searcher --bench invert`
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use matcher::{Match, Matcher, RegexMatcher};
+use searcher::{Searcher, SearcherConfig};
+
+const LINES: usize = 200_000;
+const ROUNDS: usize = 5;
+
+// 对照组：逐行判断时先找出这一行的全部匹配
+struct FindAll(RegexMatcher);
+
+impl Matcher for FindAll {
+    fn find_matches(&self, haystack: &str) -> Vec<Match> {
+        self.0.find_matches(haystack)
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        !self.0.find_matches(haystack).is_empty()
+    }
+}
+
+// 大部分行有多个匹配，少数行没有匹配（反向匹配输出的行）
+fn corpus() -> Vec<u8> {
+    let mut data = Vec::new();
+    for i in 0..LINES {
+        let line = match i % 10 {
+            0 => format!("{} plain line without the word\n", i),
+            _ => format!("{} error: disk error, retrying after error {}\n", i, i % 7),
+        };
+        data.extend_from_slice(line.as_bytes());
+    }
+    data
+}
+
+// 多轮中最快的一次，以及搜索结果的行数
+fn best_of(mut search: impl FnMut() -> usize) -> (Duration, usize) {
+    let mut best = Duration::MAX;
+    let mut lines = 0;
+    for _ in 0..ROUNDS {
+        let started = Instant::now();
+        lines = black_box(search());
+        best = best.min(started.elapsed());
+    }
+    (best, lines)
+}
+
+fn report(name: &str, bytes: usize, elapsed: Duration) {
+    let throughput = bytes as f64 / elapsed.as_secs_f64() / (1024.0 * 1024.0);
+    println!("{:<12} {:>10.2?} {:>10.1} MiB/s", name, elapsed, throughput);
+}
+
+fn main() {
+    let data = corpus();
+    let config = SearcherConfig {
+        invert_match: true,
+        ..SearcherConfig::default()
+    };
+    let pattern = "error";
+    let find_all = Searcher::with_config(FindAll(RegexMatcher::new(pattern).unwrap()), config.clone());
+    let is_match = Searcher::with_config(RegexMatcher::new(pattern).unwrap(), config);
+
+    let (before, expected) = best_of(|| find_all.search_slice_lines(&data).len());
+    let (after, lines) = best_of(|| is_match.search_slice_lines(&data).len());
+    assert_eq!(lines, expected);
+    assert_eq!(lines, LINES / 10);
+
+    report("find_matches", data.len(), before);
+    report("is_match", data.len(), after);
+    println!("speedup      {:>10.2}x", before.as_secs_f64() / after.as_secs_f64());
+}
@@ -0,0 +1,129 @@
+//! 文件编码检测与转码
+//!
+//! 搜索始终在 UTF-8 上进行；UTF-16 文件在搜索前整体转码为 UTF-8。
+
+use anyhow::{Result, bail};
+
+const UTF16LE_BOM: &[u8] = b"\xFF\xFE";
+const UTF16BE_BOM: &[u8] = b"\xFE\xFF";
+// 编码和二进制文件的启发式检测只看文件开头的这么多字节
+pub(crate) const SNIFF_LEN: usize = 4096;
+
+/// 文件编码
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// 逐个文件检测：有 BOM 按 BOM，否则检查 UTF-16 的交错 NUL 字节并要求解码后是
+    /// 可打印的文本，默认为 UTF-8
+    #[default]
+    Auto,
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Encoding {
+    /// 解析 `--encoding` 的取值（不区分大小写，`-` 可以省略）
+    pub fn from_label(label: &str) -> Result<Self> {
+        let normalized = label.to_ascii_lowercase().replace('-', "");
+        Ok(match normalized.as_str() {
+            "auto" => Encoding::Auto,
+            "utf8" => Encoding::Utf8,
+            "utf16le" => Encoding::Utf16Le,
+            "utf16be" => Encoding::Utf16Be,
+            _ => bail!("unsupported encoding: {}", label),
+        })
+    }
+
+    /// `Auto` 时根据数据开头判断实际编码，其他编码原样返回
+    pub fn detect(self, head: &[u8]) -> Self {
+        if self != Encoding::Auto {
+            return self;
+        }
+        if head.starts_with(UTF16LE_BOM) {
+            return Encoding::Utf16Le;
+        }
+        if head.starts_with(UTF16BE_BOM) {
+            return Encoding::Utf16Be;
+        }
+
+        // 没有 BOM 的 UTF-16（以 ASCII 为主的文本）：每两个字节中有一个是 NUL，
+        // 并且 NUL 几乎总是出现在同一侧。UTF-16 数据的长度总是偶数；
+        // 不足 SNIFF_LEN 的 head 就是整个文件
+        if head.len() % 2 == 1 {
+            return Encoding::Utf8;
+        }
+        let head = &head[..head.len().min(SNIFF_LEN)];
+        let pairs = head.len() / 2;
+        if pairs < 2 {
+            return Encoding::Utf8;
+        }
+        let (mut even_nul, mut odd_nul) = (0, 0);
+        for pair in head.chunks_exact(2) {
+            match (pair[0] == 0, pair[1] == 0) {
+                (true, false) => even_nul += 1,
+                (false, true) => odd_nul += 1,
+                _ => {}
+            }
+        }
+        let guess = if odd_nul * 2 >= pairs && even_nul == 0 {
+            Encoding::Utf16Le
+        } else if even_nul * 2 >= pairs && odd_nul == 0 {
+            Encoding::Utf16Be
+        } else {
+            return Encoding::Utf8;
+        };
+        // 交错的 NUL 在二进制数据（例如 16 位整数数组）中同样常见：
+        // 只有解码后几乎都是可打印文本时才按 UTF-16 处理
+        match mostly_printable(head, guess) {
+            true => guess,
+            false => Encoding::Utf8,
+        }
+    }
+
+    /// 数据是否以这种编码的 BOM 开头；`Auto` 和 UTF-8 总是返回 false
+    pub fn has_bom(self, head: &[u8]) -> bool {
+        match self {
+            Encoding::Utf16Le => head.starts_with(UTF16LE_BOM),
+            Encoding::Utf16Be => head.starts_with(UTF16BE_BOM),
+            Encoding::Auto | Encoding::Utf8 => false,
+        }
+    }
+
+    /// 是否需要在搜索前转码
+    pub fn needs_transcode(self) -> bool {
+        matches!(self, Encoding::Utf16Le | Encoding::Utf16Be)
+    }
+}
+
+// 按 UTF-16 解码后，控制字符（常见的空白除外）和非法代理对不超过 1/20
+fn mostly_printable(data: &[u8], encoding: Encoding) -> bool {
+    let decoded = transcode_to_utf8(data, encoding);
+    let text = String::from_utf8_lossy(&decoded);
+    let (mut total, mut unprintable) = (0, 0);
+    for c in text.chars() {
+        total += 1;
+        let control = c.is_control() && !matches!(c, '\t' | '\n' | '\r' | '\x0c');
+        if control || c == char::REPLACEMENT_CHARACTER {
+            unprintable += 1;
+        }
+    }
+    unprintable * 20 <= total
+}
+
+/// 把 UTF-16 数据转码为 UTF-8（去掉开头的 BOM，非法的代理对替换为 U+FFFD）
+pub fn transcode_to_utf8(data: &[u8], encoding: Encoding) -> Vec<u8> {
+    let (bom, little_endian) = match encoding {
+        Encoding::Utf16Le => (UTF16LE_BOM, true),
+        Encoding::Utf16Be => (UTF16BE_BOM, false),
+        Encoding::Auto | Encoding::Utf8 => return data.to_vec(),
+    };
+    let data = data.strip_prefix(bom).unwrap_or(data);
+    let units = data.chunks_exact(2).map(|pair| match little_endian {
+        true => u16::from_le_bytes([pair[0], pair[1]]),
+        false => u16::from_be_bytes([pair[0], pair[1]]),
+    });
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect::<String>()
+        .into_bytes()
+}
@@ -1,153 +1,1023 @@
+mod encoding;
+mod lines;
+
 use matcher::{Matcher, Match};
 use anyhow::Result;
+use std::collections::VecDeque;
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{BufRead, BufReader, ErrorKind, Read};
 use std::path::Path;
 use memmap2::Mmap;
 
+pub use encoding::Encoding;
+pub use lines::LineIter;
+
 const MMAP_THRESHOLD: u64 = 128 * 1024; // 128 KB
 const BUFFER_SIZE: usize = 64 * 1024; // 64 KB
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// 搜索器配置
+#[derive(Debug, Clone, Default)]
+pub struct SearcherConfig {
+    /// 命中行之前输出的上下文行数
+    ///
+    /// 上下文总是围绕“命中的行”：反向匹配（`invert_match`）时命中的是不匹配的行，
+    /// 它们周围真正匹配模式的行才作为上下文输出；相邻的命中行之间不会重复输出上下文
+    pub before_context: usize,
+    /// 命中行之后输出的上下文行数
+    pub after_context: usize,
+    /// 每个文件最多报告的匹配行数（`None` 表示不限制）。
+    /// 反向匹配时统计的是不匹配的行；所有输出模式都在这里统一截断。
+    /// 计数器属于单次搜索调用，搜索目录时每个文件都从零开始计数，
+    /// 与遍历方式（顺序、并行）无关
+    pub max_count: Option<usize>,
+    /// 反向匹配：不匹配的行才是命中的行（`-v`）
+    pub invert_match: bool,
+    /// 文件编码；默认逐个文件自动检测
+    pub encoding: Encoding,
+    /// 以 NUL 而不是换行分隔记录（`--null-data`），记录序号作为行号
+    pub null_data: bool,
+    /// `Match.content` 是否保留原始的行结束符（`\n` / `\r\n`），便于按字节还原文件。
+    /// 默认去掉；`Line.content` 始终不含行结束符
+    pub include_line_terminator: bool,
+    /// 开头（前 4 KB）含 NUL 字节的数据视为二进制文件，不搜索（`--null-data` 时不检测）
+    pub binary_detection: bool,
+    /// 每个文件（数据源）只读取并搜索前这么多字节（`None` 表示不限制）。
+    /// 在上限处被截断的行按没有行结束符的最后一行处理，行号只反映读到的部分
+    pub max_read_bytes: Option<usize>,
+    /// 多行模式（`-U`）：在整个文件（数据源）上运行匹配器，匹配可以跨越行结束符。
+    /// 匹配覆盖的每一行都是命中的行，行中的 `Match` 是匹配落在该行内的部分。
+    /// 数据不是合法的 UTF-8 时退回逐行搜索
+    pub multiline: bool,
+    /// 文件大于这个字节数时使用 mmap，否则按块读取（`None` 使用默认的 128 KB）。
+    /// 两种方式的输出完全相同：行号和行首偏移（`Line.offset`）都是相对于整个文件的绝对值
+    pub mmap_threshold: Option<u64>,
+    /// 只需要知道哪些行命中，不需要匹配的位置（例如 `-l`）：逐行只调用可以提前返回的
+    /// `is_match`，命中行的 `matches` 为空。多行模式仍然需要匹配位置来确定命中的行
+    pub lines_only: bool,
+}
+
+impl SearcherConfig {
+    /// 分隔行（记录）的字节
+    pub fn line_terminator(&self) -> u8 {
+        if self.null_data { b'\0' } else { b'\n' }
+    }
+
+    // 按 max_read_bytes 截断内存中的数据
+    fn read_limit<'d>(&self, data: &'d [u8]) -> &'d [u8] {
+        match self.max_read_bytes {
+            Some(max) if max < data.len() => &data[..max],
+            _ => data,
+        }
+    }
+
+    // 根据数据开头判断是否是需要跳过的二进制数据；不论 `head` 有多长都只看前
+    // `SNIFF_LEN` 字节，mmap、按块读取和标准输入的判断结果一致
+    fn is_binary(&self, head: &[u8]) -> bool {
+        let head = &head[..head.len().min(encoding::SNIFF_LEN)];
+        self.binary_detection && !self.null_data && head.contains(&0)
+    }
+}
+
+/// 输出行的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    /// 命中的行：包含匹配的行，反向匹配时是不包含匹配的行
+    Match,
+    /// 匹配行周围的上下文行
+    Context,
+}
+
+/// 搜索结果中的一行
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub number: usize,
+    /// 行首在文件中的字节偏移（开头的 BOM 计算在内；UTF-16 文件为转码后的偏移）
+    pub offset: usize,
+    pub content: String,
+    pub kind: LineKind,
+    /// 该行上的所有匹配（上下文行以及反向匹配命中的行为空）
+    pub matches: Vec<Match>,
+}
+
+/// 一次搜索（一个文件或数据源）处理的数据量，供 `--stats` 等统计使用
+///
+/// 只统计实际读取并逐行处理过的部分：二进制文件为 0，达到 max-count 提前停止时
+/// 只算到最后处理的那一行；完整搜索的文件 `bytes_searched` 等于文件大小
+/// （UTF-16 文件为原始字节数）。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// 处理过的字节数（包括行结束符和开头的 BOM）
+    pub bytes_searched: u64,
+    /// 处理过的行数
+    pub lines_searched: u64,
+}
+
+impl SearchStats {
+    /// 累加另一次搜索的统计
+    pub fn add(&mut self, other: &SearchStats) {
+        self.bytes_searched += other.bytes_searched;
+        self.lines_searched += other.lines_searched;
+    }
+
+    // 记录处理过的一行（`len` 包括行结束符）
+    fn record_line(&mut self, len: usize) {
+        self.bytes_searched += len as u64;
+        self.lines_searched += 1;
+    }
+}
+
+impl Line {
+    fn context(number: usize, offset: usize, content: &str) -> Self {
+        Line {
+            number,
+            offset,
+            content: content.to_string(),
+            kind: LineKind::Context,
+            matches: Vec::new(),
+        }
+    }
+}
+
+// 按 UTF-8 解码 `LineIter` 产生的一行，非法 UTF-8 的行返回 None。
+// 以换行分隔时去掉行尾的 `\r`；NUL 分隔的记录保持原样。
+// 同时返回被去掉的行尾（数据末尾没有行结束符的最后一行为空或 `\r`）
+fn decode_line(bytes: &[u8], terminator: u8) -> Option<(&str, &'static str)> {
+    let (bytes, terminated) = match bytes.strip_suffix(&[terminator]) {
+        Some(stripped) => (stripped, true),
+        None => (bytes, false),
+    };
+    let (bytes, ending) = match (terminator, terminated) {
+        (b'\n', _) => match (bytes.strip_suffix(b"\r"), terminated) {
+            (Some(stripped), true) => (stripped, "\r\n"),
+            // 没有行结束符的最后一行同样去掉 `\r`
+            (Some(stripped), false) => (stripped, "\r"),
+            (None, true) => (bytes, "\n"),
+            (None, false) => (bytes, ""),
+        },
+        (_, true) => (bytes, "\0"),
+        (_, false) => (bytes, ""),
+    };
+    std::str::from_utf8(bytes).ok().map(|text| (text, ending))
+}
+
+// 逐行处理：命中判定（含反向匹配）、max-count 上限以及上下文窗口。
+// max-count 只在这里执行，计数、-o 等输出模式看到的都是已经截断的结果
+struct LineCollector<'a, M: Matcher> {
+    matcher: &'a M,
+    config: &'a SearcherConfig,
+    // 尚未输出的前置上下文
+    before: VecDeque<Line>,
+    // 还需要输出的后置上下文行数
+    after_remaining: usize,
+    // 本次搜索（一个文件或数据源）中命中的行数，max-count 按它截断
+    match_count: usize,
+    lines: Vec<Line>,
+}
+
+impl<'a, M: Matcher> LineCollector<'a, M> {
+    fn new(matcher: &'a M, config: &'a SearcherConfig) -> Self {
+        LineCollector {
+            matcher,
+            config,
+            before: VecDeque::new(),
+            after_remaining: 0,
+            match_count: 0,
+            lines: Vec::new(),
+        }
+    }
+
+    fn limit_reached(&self) -> bool {
+        self.config.max_count.is_some_and(|max| self.match_count >= max)
+    }
+
+    /// 处理一行，返回 false 表示后续的行都不再需要。
+    /// `offset` 是行首的字节偏移，`ending` 是这一行原本的行结束符
+    /// （`include_line_terminator` 时保留在匹配内容中）
+    fn feed(&mut self, number: usize, offset: usize, text: &str, ending: &str) -> bool {
+        if let Some(more) = self.after_limit(number, offset, text) {
+            return more;
+        }
+
+        // 反向匹配只需要知道这一行是否匹配，用可以提前返回的 is_match 即可；
+        // 命中的行本来就不包含匹配，也不需要匹配位置
+        let mut matches = Vec::new();
+        let hit = if self.config.invert_match {
+            !self.matcher.is_match(text)
+        } else if self.config.lines_only {
+            self.matcher.is_match(text)
+        } else {
+            matches = self.matcher.find_matches(text);
+            !matches.is_empty()
+        };
+        self.record(number, offset, text, ending, hit, matches)
+    }
+
+    /// 与 `feed` 相同，但这一行上的匹配已经找好（多行模式在整段数据上匹配）
+    fn feed_matched(&mut self, number: usize, offset: usize, text: &str, ending: &str, matches: Vec<Match>) -> bool {
+        if let Some(more) = self.after_limit(number, offset, text) {
+            return more;
+        }
+        let hit = matches.is_empty() == self.config.invert_match;
+        let matches = if self.config.invert_match { Vec::new() } else { matches };
+        self.record(number, offset, text, ending, hit, matches)
+    }
+
+    // 达到 max-count 之后不再统计匹配，只把最后一个匹配的后置上下文输出完；
+    // 返回 None 表示还没有达到上限
+    fn after_limit(&mut self, number: usize, offset: usize, text: &str) -> Option<bool> {
+        if !self.limit_reached() {
+            return None;
+        }
+        if self.after_remaining == 0 {
+            return Some(false);
+        }
+        self.after_remaining -= 1;
+        self.lines.push(Line::context(number, offset, text));
+        Some(self.after_remaining > 0)
+    }
+
+    // 记录一行：命中的行连同等待中的前置上下文一起输出，其余的行按需作为上下文
+    fn record(&mut self, number: usize, offset: usize, text: &str, ending: &str, hit: bool, mut matches: Vec<Match>) -> bool {
+        if hit {
+            let content = if self.config.include_line_terminator {
+                format!("{}{}", text, ending)
+            } else {
+                text.to_string()
+            };
+            for mat in &mut matches {
+                mat.line = number;
+                mat.content = content.clone();
+            }
+            self.lines.extend(self.before.drain(..));
+            self.lines.push(Line {
+                number,
+                offset,
+                content: text.to_string(),
+                kind: LineKind::Match,
+                matches,
+            });
+            self.match_count += 1;
+            self.after_remaining = self.config.after_context;
+            return !(self.limit_reached() && self.after_remaining == 0);
+        }
+
+        if self.after_remaining > 0 {
+            self.after_remaining -= 1;
+            self.lines.push(Line::context(number, offset, text));
+        } else if self.config.before_context > 0 {
+            self.before.push_back(Line::context(number, offset, text));
+            if self.before.len() > self.config.before_context {
+                self.before.pop_front();
+            }
+        }
+        true
+    }
+
+    fn finish(self) -> Vec<Line> {
+        self.lines
+    }
+
+    // 取走目前已经确定要输出的行（流式搜索时逐批交给调用方）；
+    // 还在等待匹配的前置上下文留在 collector 中
+    fn take_lines(&mut self) -> Vec<Line> {
+        std::mem::take(&mut self.lines)
+    }
+}
 
 pub struct Searcher <M: Matcher> {
     matcher: M,
+    config: SearcherConfig,
 }
 
 impl<M: Matcher> Searcher<M> {
     pub fn new(matcher: M) -> Self {
-        Searcher { matcher }
+        Self::with_config(matcher, SearcherConfig::default())
+    }
+
+    pub fn with_config(matcher: M, config: SearcherConfig) -> Self {
+        Searcher { matcher, config }
+    }
+
+    pub fn matcher(&self) -> &M {
+        &self.matcher
+    }
+
+    pub fn config(&self) -> &SearcherConfig {
+        &self.config
     }
 
 
     // 1. 添加 should_use_mmap 函数
-    fn should_use_mmap(path: &Path) -> Result<bool> {
+    fn should_use_mmap(&self, path: &Path) -> Result<bool> {
         let metadata = std::fs::metadata(path)?;
         let file_size = metadata.len();
-        Ok(file_size > MMAP_THRESHOLD)
+        Ok(file_size > self.config.mmap_threshold.unwrap_or(MMAP_THRESHOLD))
     }
 
     // 2. 修改 search_file_mmap，处理最后一行
-    fn search_file_mmap(&self, path: &Path) -> Result<Vec<Match>> {
+    fn search_file_mmap(&self, path: &Path, stats: &mut SearchStats) -> Result<Vec<Line>> {
         let file = File::open(path)?;
         // SAFETY: 文件在映射期间是只读的，映射的生命周期由 Mmap 管理
         let mmap = unsafe { Mmap::map(&file)? };
+        Ok(self.search_slice(self.config.read_limit(&mmap), stats))
+    }
 
-        let mut all_matches = Vec::new();
-        let mut line_num = 1;
-        let mut start = 0;
-
-        for i in 0..mmap.len() {
-            if mmap[i] == b'\n' {
-                let line_bytes = &mmap[start..i];
-                if let Ok(line) = std::str::from_utf8(line_bytes) {
-                    let mut matches = self.matcher.find_matches(line);
-                    for mat in &mut matches {
-                        mat.line = line_num;
-                        mat.content = line.to_string();
-                    }
-                    all_matches.extend(matches);
-                }
-                start = i + 1;
-                line_num += 1;
+    /// 在调用方提供的内存（例如已有的 mmap 或字节缓冲区）上搜索，
+    /// 返回匹配行以及按配置需要输出的上下文行
+    pub fn search_slice_lines(&self, data: &[u8]) -> Vec<Line> {
+        self.search_slice(data, &mut SearchStats::default())
+    }
+
+    fn search_slice(&self, data: &[u8], stats: &mut SearchStats) -> Vec<Line> {
+        // BOM 只在数据开头去掉一次，文件中间出现的同样字节属于内容本身；
+        // 行的字节偏移仍然相对于原始数据
+        let (data, base) = match data.strip_prefix(UTF8_BOM) {
+            Some(rest) => (rest, UTF8_BOM.len()),
+            None => (data, 0),
+        };
+        stats.bytes_searched += base as u64;
+        if self.config.multiline
+            && let Ok(text) = std::str::from_utf8(data)
+        {
+            return self.search_multiline(text, base, stats);
+        }
+        let mut collector = LineCollector::new(&self.matcher, &self.config);
+        let terminator = self.config.line_terminator();
+        for (line_num, offset, bytes) in LineIter::with_position(data, terminator, 1, base) {
+            stats.record_line(bytes.len());
+            if let Some((line, ending)) = decode_line(bytes, terminator)
+                && !collector.feed(line_num, offset, line, ending)
+            {
+                break;
             }
         }
 
-        // 处理最后一行（如果文件不以换行符结尾）
-        if start < mmap.len() {
-            let line_bytes = &mmap[start..];
-            if let Ok(line) = std::str::from_utf8(line_bytes) {
-                let mut matches = self.matcher.find_matches(line);
-                for mat in &mut matches {
-                    mat.line = line_num;
-                    mat.content = line.to_string();
-                }
-                all_matches.extend(matches);
+        collector.finish()
+    }
+
+    // 多行模式：在整段文本上匹配，再把每个匹配按行拆分，交给逐行的命中判定和上下文处理。
+    // `base` 是 text 在原始数据中的偏移（开头的 BOM）
+    fn search_multiline(&self, text: &str, base: usize, stats: &mut SearchStats) -> Vec<Line> {
+        let matches = self.matcher.find_matches(text);
+        let mut collector = LineCollector::new(&self.matcher, &self.config);
+        let terminator = self.config.line_terminator();
+        // 第一个可能覆盖当前行的匹配；匹配互不重叠并按位置排列，结束位置同样递增
+        let mut first = 0;
+        for (line_num, offset, bytes) in LineIter::with_position(text.as_bytes(), terminator, 1, 0) {
+            stats.record_line(bytes.len());
+            let Some((line, ending)) = decode_line(bytes, terminator) else {
+                continue;
+            };
+            // 匹配的最后一个字节（零宽匹配为起点）在这一行之前的，不再覆盖之后的行
+            while first < matches.len() && matches[first].end.max(matches[first].start + 1) <= offset {
+                first += 1;
+            }
+            let content_end = offset + line.len();
+            let on_line: Vec<Match> = matches[first..]
+                .iter()
+                .take_while(|m| m.start < offset + bytes.len())
+                .map(|m| {
+                    // 只保留落在这一行内容中的部分（不含行结束符）
+                    let start = m.start.clamp(offset, content_end) - offset;
+                    let end = m.end.clamp(offset + start, content_end) - offset;
+                    Match::new(start, end, line_num, line[start..end].to_string())
+                })
+                .collect();
+            if !collector.feed_matched(line_num, base + offset, line, ending, on_line) {
+                break;
             }
         }
+        collector.finish()
+    }
 
-        Ok(all_matches)
+    /// 按字节搜索整段数据（不分行、不检测二进制），返回每个匹配在数据中的字节范围。
+    /// 用于 `--hex`；`max_count` 限制的是匹配个数，`max_read_bytes` 限制搜索的范围
+    pub fn search_bytes(&self, data: &[u8]) -> Vec<(usize, usize)> {
+        let mut matches = self.matcher.find_bytes(self.config.read_limit(data));
+        if let Some(max) = self.config.max_count {
+            matches.truncate(max);
+        }
+        matches
     }
 
-    // 3. 实现块读取的缓冲搜索函数
-    fn search_file_buffered(&self, path: &Path) -> Result<Vec<Match>> {
-        let file = File::open(path)?;
-        let mut reader = BufReader::with_capacity(BUFFER_SIZE, file);
-        
-        let mut all_matches = Vec::new();
+    /// 在调用方提供的内存上搜索，只返回匹配
+    pub fn search_mmap(&self, data: &[u8]) -> Vec<Match> {
+        matches_of(self.search_slice_lines(data))
+    }
+
+    // 3. 实现块读取的缓冲搜索函数：行首偏移跨块累加（buffer_offset），
+    //    与 mmap 路径中直接取到的位置一致
+    fn search_file_buffered(&self, path: &Path, stats: &mut SearchStats) -> Result<Vec<Line>> {
+        self.search_reader_with_stats(File::open(path)?, stats)
+    }
+
+    /// 从任意数据源（例如标准输入）按块读取并搜索
+    pub fn search_reader<R: Read>(&self, reader: R) -> Result<Vec<Line>> {
+        self.search_reader_with_stats(reader, &mut SearchStats::default())
+    }
+
+    /// 与 `search_reader` 相同，同时把处理的数据量累加到 `stats`
+    pub fn search_reader_with_stats<R: Read>(&self, reader: R, stats: &mut SearchStats) -> Result<Vec<Line>> {
+        let mut lines = Vec::new();
+        self.search_reader_streaming(reader, stats, |batch| {
+            lines.extend(batch);
+            Ok(())
+        })?;
+        Ok(lines)
+    }
+
+    /// 流式搜索：每读到一块数据就处理其中完整的行，并把已经确定的结果行
+    /// 交给 `sink`，不等待数据源结束。适合不会结束的输入（`tail -f | grepdojo`）。
+    ///
+    /// 检测二进制数据时先读满开头的检测窗口（4 KB，或读到数据结束）再开始输出；
+    /// 之后每次读取只等待数据源当前能提供的数据，不会为了填满缓冲区而阻塞。
+    /// 同一数据源的多批结果按顺序交付，行号和偏移在批次之间连续。
+    /// 处理的数据量累加到 `stats`
+    pub fn search_reader_streaming<R, F>(&self, reader: R, stats: &mut SearchStats, mut sink: F) -> Result<()>
+    where
+        R: Read,
+        F: FnMut(Vec<Line>) -> Result<()>,
+    {
+        let limit = self.config.max_read_bytes.map_or(u64::MAX, |max| max as u64);
+        let mut reader = BufReader::with_capacity(BUFFER_SIZE, reader.take(limit));
+        // 多行匹配需要完整的数据，读完之后一次性搜索
+        if self.config.multiline {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data)?;
+            if self.config.is_binary(&data) {
+                return Ok(());
+            }
+            return sink(self.search_slice(&data, stats));
+        }
+        // 二进制检测和 BOM 都只看数据开头，与 mmap 路径使用同样的窗口。管道的一次读取
+        // 可能只有几个字节，先读满窗口（或读到数据结束）再判断
+        let wanted = match self.config.binary_detection && !self.config.null_data {
+            true => encoding::SNIFF_LEN,
+            false => UTF8_BOM.len(),
+        };
+        let mut head = Vec::with_capacity(wanted);
+        while head.len() < wanted {
+            let available = match reader.fill_buf() {
+                Ok(available) => available,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            if available.is_empty() {
+                break;
+            }
+            let n = available.len().min(wanted - head.len());
+            head.extend_from_slice(&available[..n]);
+            reader.consume(n);
+        }
+        if self.config.is_binary(&head) {
+            return Ok(());
+        }
+        // 与 search_slice_lines 一致：只去掉数据开头的 BOM；已经读出的数据接在剩余数据之前。
+        // buffer_offset 是当前 buffer 第一个字节在数据中的偏移
+        let mut buffer_offset = match head.starts_with(UTF8_BOM) {
+            true => UTF8_BOM.len(),
+            false => 0,
+        };
+        stats.bytes_searched += buffer_offset as u64;
+        let mut reader = (&head[buffer_offset..]).chain(reader);
+
+        let mut collector = LineCollector::new(&self.matcher, &self.config);
+        let terminator = self.config.line_terminator();
         let mut line_num = 1;
         let mut carryover = Vec::new();
-        
+
         // 块读取循环
         loop {
             let mut buffer = vec![0u8; BUFFER_SIZE];
-            let bytes_read = reader.read(&mut buffer)?;
-            
+            let bytes_read = match reader.read(&mut buffer) {
+                Ok(n) => n,
+                // 被信号打断的读取不代表文件结束，重试即可
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            // 只有读到 0 字节才表示文件结束；文件大小恰好是块大小的整数倍时，
+            // 最后一次读取的数据仍留在 carryover 中，由循环后的代码处理
             if bytes_read == 0 {
                 break; // 文件读取完毕
             }
-            
+
             buffer.truncate(bytes_read);
-            
+
             // 处理跨块数据：将 carryover 的内容添加到 buffer 前面
             if !carryover.is_empty() {
-                let mut combined = carryover;
+                let mut combined = std::mem::take(&mut carryover);
                 combined.extend_from_slice(&buffer);
                 buffer = combined;
-                carryover = Vec::new(); // 重新初始化 carryover
             }
-            
+
             // 查找最后一个换行符
-            let mut last_newline = None;
-            for i in (0..buffer.len()).rev() {
-                if buffer[i] == b'\n' {
-                    last_newline = Some(i);
-                    break;
-                }
-            }
-            
+            let last_newline = memchr::memrchr(terminator, &buffer);
+
             // 分割完整行和剩余数据
             if let Some(newline_pos) = last_newline {
-                let complete_lines = &buffer[..=newline_pos];
                 carryover = buffer[newline_pos + 1..].to_vec();
-                
-                // 处理完整行
-                if let Ok(text) = std::str::from_utf8(complete_lines) {
-                    for line in text.lines() {
-                        let mut matches = self.matcher.find_matches(line);
-                        for mat in &mut matches {
-                            mat.line = line_num;
-                            mat.content = line.to_string();
-                        }
-                        all_matches.extend(matches);
-                        line_num += 1;
+
+                // 处理完整行（逐行解码，非法 UTF-8 的行跳过但仍计入行号和偏移）
+                let mut lines = LineIter::with_position(&buffer[..=newline_pos], terminator, line_num, buffer_offset);
+                for (number, offset, bytes) in &mut lines {
+                    stats.record_line(bytes.len());
+                    if let Some((line, ending)) = decode_line(bytes, terminator)
+                        && !collector.feed(number, offset, line, ending)
+                    {
+                        return sink(collector.finish());
                     }
                 }
+                line_num = lines.line_number();
+                buffer_offset += newline_pos + 1;
+
+                let batch = collector.take_lines();
+                if !batch.is_empty() {
+                    sink(batch)?;
+                }
             } else {
                 // 没有换行符，整个块是不完整的行
                 carryover = buffer;
             }
         }
-        
-        // 处理文件末尾的剩余数据
-        if !carryover.is_empty() {
-            if let Ok(line) = std::str::from_utf8(&carryover) {
-                let mut matches = self.matcher.find_matches(line);
-                for mat in &mut matches {
-                    mat.line = line_num;
-                    mat.content = line.to_string();
-                }
-                all_matches.extend(matches);
+
+        // 处理文件末尾的剩余数据（没有换行符结尾的最后一行，恰好搜索一次）
+        for (number, offset, bytes) in LineIter::with_position(&carryover, terminator, line_num, buffer_offset) {
+            stats.record_line(bytes.len());
+            if let Some((line, ending)) = decode_line(bytes, terminator) {
+                collector.feed(number, offset, line, ending);
             }
         }
-        
-        Ok(all_matches)
+
+        let batch = collector.finish();
+        if !batch.is_empty() {
+            sink(batch)?;
+        }
+        Ok(())
     }
 
-    pub fn search_file(&self, path: &Path) -> Result<Vec<Match>> {
+    /// 搜索文件，返回匹配行以及按配置需要输出的上下文行
+    pub fn search_file_lines(&self, path: &Path) -> Result<Vec<Line>> {
+        self.search_file_lines_with_stats(path, &mut SearchStats::default())
+    }
+
+    /// 与 `search_file_lines` 相同，同时把处理的数据量累加到 `stats`
+    pub fn search_file_lines_with_stats(&self, path: &Path, stats: &mut SearchStats) -> Result<Vec<Line>> {
+        // 先确定编码：UTF-16 文件整体读入并转码为 UTF-8 后再搜索
+        let head = read_head(path)?;
+        let encoding = self.config.encoding.detect(&head);
+        if encoding.needs_transcode() {
+            let data = std::fs::read(path)?;
+            let data = self.config.read_limit(&data);
+            // 行数来自转码后的数据，字节数按原始数据计算
+            let mut transcoded = SearchStats::default();
+            let text = encoding::transcode_to_utf8(data, encoding);
+            // 没有 BOM 的文件只是按内容猜测的编码，解码后含 NUL 的仍然是二进制文件
+            if !encoding.has_bom(&head) && self.config.is_binary(&text) {
+                return Ok(Vec::new());
+            }
+            let lines = self.search_slice(&text, &mut transcoded);
+            stats.lines_searched += transcoded.lines_searched;
+            stats.bytes_searched += data.len() as u64;
+            return Ok(lines);
+        }
+        // UTF-16 之外含 NUL 的文件是二进制文件
+        if self.config.is_binary(&head) {
+            return Ok(Vec::new());
+        }
+
         // 根据文件大小选择策略
-        if Self::should_use_mmap(path)? {
-            self.search_file_mmap(path)
+        if self.should_use_mmap(path)? {
+            self.search_file_mmap(path, stats)
         } else {
-            self.search_file_buffered(path)
+            self.search_file_buffered(path, stats)
+        }
+    }
+
+    pub fn search_file(&self, path: &Path) -> Result<Vec<Match>> {
+        Ok(matches_of(self.search_file_lines(path)?))
+    }
+}
+
+// 读取文件开头用于编码检测的字节
+fn read_head(path: &Path) -> Result<Vec<u8>> {
+    let mut head = Vec::with_capacity(encoding::SNIFF_LEN);
+    File::open(path)?
+        .take(encoding::SNIFF_LEN as u64)
+        .read_to_end(&mut head)?;
+    Ok(head)
+}
+
+// 从输出行中取出所有匹配（忽略上下文行）
+fn matches_of(lines: Vec<Line>) -> Vec<Match> {
+    lines
+        .into_iter()
+        .filter(|line| line.kind == LineKind::Match)
+        .flat_map(|line| line.matches)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matcher::RegexMatcher;
+    use std::io;
+
+    fn searcher(pattern: &str, config: SearcherConfig) -> Searcher<RegexMatcher> {
+        Searcher::with_config(RegexMatcher::new(pattern).unwrap(), config)
+    }
+
+    fn numbered(lines: &[Line]) -> Vec<(usize, &str)> {
+        lines.iter().map(|line| (line.number, line.content.as_str())).collect()
+    }
+
+    // 按给定的顺序依次返回数据块或被信号打断的错误
+    struct ChunkedReader {
+        chunks: VecDeque<Option<&'static [u8]>>,
+    }
+
+    impl ChunkedReader {
+        // `None` 表示这一次读取返回 `ErrorKind::Interrupted`
+        fn new(chunks: &[Option<&'static [u8]>]) -> Self {
+            ChunkedReader {
+                chunks: chunks.iter().copied().collect(),
+            }
+        }
+    }
+
+    impl Read for ChunkedReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.chunks.pop_front() {
+                None => Ok(0),
+                Some(None) => Err(io::Error::from(ErrorKind::Interrupted)),
+                Some(Some(chunk)) => {
+                    // 测试中的块都小于缓冲区
+                    buf[..chunk.len()].copy_from_slice(chunk);
+                    Ok(chunk.len())
+                }
+            }
+        }
+    }
+
+    // 记录 find_matches / is_match 各被调用了多少次
+    #[derive(Default)]
+    struct CountingMatcher {
+        find_calls: std::cell::Cell<usize>,
+        is_match_calls: std::cell::Cell<usize>,
+    }
+
+    impl Matcher for CountingMatcher {
+        fn find_matches(&self, haystack: &str) -> Vec<Match> {
+            self.find_calls.set(self.find_calls.get() + 1);
+            haystack
+                .find("hit")
+                .map(|start| Match::new(start, start + 3, 0, "hit".to_string()))
+                .into_iter()
+                .collect()
+        }
+
+        fn is_match(&self, haystack: &str) -> bool {
+            self.is_match_calls.set(self.is_match_calls.get() + 1);
+            haystack.contains("hit")
+        }
+    }
+
+    const COUNTED: &[u8] = b"hit\nmiss\nhit again\nmiss\n";
+
+    #[test]
+    fn invert_match_only_calls_is_match() {
+        let config = SearcherConfig {
+            invert_match: true,
+            before_context: 1,
+            ..SearcherConfig::default()
+        };
+        let searcher = Searcher::with_config(CountingMatcher::default(), config);
+        let lines = searcher.search_slice_lines(COUNTED);
+        assert_eq!(numbered(&lines), [(1, "hit"), (2, "miss"), (3, "hit again"), (4, "miss")]);
+        assert_eq!(searcher.matcher().find_calls.get(), 0);
+        assert_eq!(searcher.matcher().is_match_calls.get(), 4);
+    }
+
+    #[test]
+    fn lines_only_calls_is_match() {
+        let config = SearcherConfig {
+            lines_only: true,
+            ..SearcherConfig::default()
+        };
+        let searcher = Searcher::with_config(CountingMatcher::default(), config);
+        let lines = searcher.search_reader(COUNTED).unwrap();
+        assert_eq!(numbered(&lines), [(1, "hit"), (3, "hit again")]);
+        assert!(lines.iter().all(|line| line.matches.is_empty()));
+        assert_eq!(searcher.matcher().find_calls.get(), 0);
+        assert_eq!(searcher.matcher().is_match_calls.get(), 4);
+    }
+
+    #[test]
+    fn normal_search_finds_match_positions() {
+        let searcher = Searcher::new(CountingMatcher::default());
+        let lines = searcher.search_slice_lines(COUNTED);
+        assert_eq!(lines[1].matches[0].start, 0);
+        assert_eq!(searcher.matcher().find_calls.get(), 4);
+        assert_eq!(searcher.matcher().is_match_calls.get(), 0);
+    }
+
+    // 同一个文件分别用 mmap 和按块读取搜索
+    fn search_both_ways(data: &[u8], config: SearcherConfig) -> (Vec<Line>, Vec<Line>) {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        io::Write::write_all(&mut file, data).unwrap();
+        let search = |threshold| {
+            let config = SearcherConfig {
+                mmap_threshold: Some(threshold),
+                ..config.clone()
+            };
+            searcher("hit", config).search_file_lines(file.path()).unwrap()
+        };
+        (search(0), search(u64::MAX))
+    }
+
+    fn positions(lines: &[Line]) -> Vec<(usize, usize, LineKind, &str)> {
+        lines
+            .iter()
+            .map(|line| (line.number, line.offset, line.kind, line.content.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn mmap_and_buffered_report_the_same_offsets() {
+        // 多个块大小的数据，命中行跨越块边界；开头有 BOM，行结束符混用 `\r\n`
+        let mut data = UTF8_BOM.to_vec();
+        for i in 0..20_000 {
+            let line = match i % 997 {
+                0 => format!("hit {}\r\n", i),
+                _ => format!("line {} {}\n", i, "x".repeat(i % 13)),
+            };
+            data.extend_from_slice(line.as_bytes());
+        }
+        data.extend_from_slice(b"last hit");
+        assert!(data.len() > 3 * BUFFER_SIZE);
+
+        let config = SearcherConfig {
+            before_context: 1,
+            after_context: 1,
+            ..SearcherConfig::default()
+        };
+        let (mmap, buffered) = search_both_ways(&data, config);
+        assert_eq!(positions(&mmap), positions(&buffered));
+        assert_eq!(mmap.iter().filter(|line| line.kind == LineKind::Match).count(), 22);
+        for line in &mmap {
+            assert!(data[line.offset..].starts_with(line.content.as_bytes()));
+        }
+    }
+
+    #[test]
+    fn search_mmap_on_a_provided_slice() {
+        let data = b"miss\nhit one\nmiss hit hit\n";
+        let searcher = searcher("hit", SearcherConfig::default());
+        let found: Vec<(usize, usize, usize)> =
+            searcher.search_mmap(data).iter().map(|m| (m.line, m.start, m.end)).collect();
+        assert_eq!(found, [(2, 0, 3), (3, 5, 8), (3, 9, 12)]);
+
+        // 与映射同样内容的文件得到相同的结果
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        io::Write::write_all(&mut file, data).unwrap();
+        let config = SearcherConfig {
+            mmap_threshold: Some(0),
+            ..SearcherConfig::default()
+        };
+        let from_file = Searcher::with_config(RegexMatcher::new("hit").unwrap(), config)
+            .search_file(file.path())
+            .unwrap();
+        assert_eq!(searcher.search_mmap(data), from_file);
+    }
+
+    #[test]
+    fn max_count_caps_matching_lines_in_every_mode() {
+        let data = b"hit 1\nmiss 2\nhit hit 3\nmiss 4\nhit 5\nmiss 6\n";
+        let search = |invert_match, lines_only| {
+            let config = SearcherConfig {
+                max_count: Some(2),
+                invert_match,
+                lines_only,
+                ..SearcherConfig::default()
+            };
+            let searcher = searcher("hit", config);
+            let slice = searcher.search_slice_lines(data);
+            // 按块读取得到相同的结果
+            assert_eq!(positions(&slice), positions(&searcher.search_reader(&data[..]).unwrap()));
+            slice.iter().map(|line| line.number).collect::<Vec<_>>()
+        };
+        // 上限计的是行，一行中的多个匹配只算一次
+        assert_eq!(search(false, false), [1, 3]);
+        assert_eq!(search(false, true), [1, 3]);
+        // 反向匹配时计的是不匹配的行
+        assert_eq!(search(true, false), [2, 4]);
+    }
+
+    #[test]
+    fn match_content_with_and_without_line_terminator() {
+        let data = b"hit 1\nmiss\nhit 2\r\nhit 3";
+        let contents = |include_line_terminator| {
+            let config = SearcherConfig {
+                include_line_terminator,
+                ..SearcherConfig::default()
+            };
+            let searcher = searcher("hit", config);
+            let lines = searcher.search_reader(&data[..]).unwrap();
+            // 行的内容始终不含行结束符
+            assert_eq!(numbered(&lines), [(1, "hit 1"), (3, "hit 2"), (4, "hit 3")]);
+            assert_eq!(searcher.search_mmap(data), matches_of(lines.clone()));
+            matches_of(lines).into_iter().map(|m| m.content).collect::<Vec<_>>()
+        };
+        assert_eq!(contents(false), ["hit 1", "hit 2", "hit 3"]);
+        // 保留原本的 `\n` / `\r\n`，最后一行没有行结束符
+        assert_eq!(contents(true), ["hit 1\n", "hit 2\r\n", "hit 3"]);
+    }
+
+    // 记录已经读取了几块数据，用来判断结果是在什么时候交付的
+    struct ReadCounter {
+        inner: ChunkedReader,
+        reads: std::rc::Rc<std::cell::Cell<usize>>,
+    }
+
+    impl Read for ReadCounter {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            if n > 0 {
+                self.reads.set(self.reads.get() + 1);
+            }
+            Ok(n)
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn streaming_delivers_lines_as_they_arrive() {
+        let reads = std::rc::Rc::new(std::cell::Cell::new(0));
+        let reader = ReadCounter {
+            inner: ChunkedReader::new(&[Some(b"hit 1\n"), Some(b"miss\n"), Some(b"hit"), Some(b" 2\nmiss\n")]),
+            reads: reads.clone(),
+        };
+        let mut delivered = Vec::new();
+        searcher("hit", SearcherConfig::default())
+            .search_reader_streaming(reader, &mut SearchStats::default(), |batch| {
+                delivered.push((reads.get(), numbered(&batch).iter().map(|&(n, _)| n).collect::<Vec<_>>()));
+                Ok(())
+            })
+            .unwrap();
+        // 每一行在它所在的数据块读到后立即交付，不等待缓冲区填满或数据结束；
+        // 不完整的行等到行结束符到达后才交付
+        assert_eq!(delivered, [(1, vec![1]), (4, vec![3])]);
+    }
+
+    #[test]
+    fn stats_count_every_byte_of_a_fully_searched_file() {
+        let mut data = UTF8_BOM.to_vec();
+        for i in 0..10_000 {
+            data.extend_from_slice(format!("line {}\r\n", i).as_bytes());
+        }
+        data.extend_from_slice(b"last hit");
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        io::Write::write_all(&mut file, &data).unwrap();
+
+        for threshold in [0, u64::MAX] {
+            let stats = |max_count| {
+                let config = SearcherConfig {
+                    mmap_threshold: Some(threshold),
+                    max_count,
+                    ..SearcherConfig::default()
+                };
+                let mut stats = SearchStats::default();
+                searcher("hit|line 5$", config).search_file_lines_with_stats(file.path(), &mut stats).unwrap();
+                stats
+            };
+            let full = stats(None);
+            assert_eq!(full.bytes_searched, data.len() as u64, "threshold {}", threshold);
+            assert_eq!(full.lines_searched, 10_001);
+            // 达到 max-count 提前停止时只算到最后处理的那一行
+            let stopped = stats(Some(1));
+            assert_eq!(stopped.lines_searched, 6);
+            assert_eq!(stopped.bytes_searched, (UTF8_BOM.len() + 6 * "line 0\r\n".len()) as u64);
+        }
+    }
+
+    // NUL 在检测窗口之外时不是二进制文件，两种读取方式的判断相同
+    #[test]
+    fn binary_detection_uses_one_window() {
+        let config = SearcherConfig {
+            binary_detection: true,
+            ..SearcherConfig::default()
+        };
+        let mut late_nul = b"hit\n".to_vec();
+        late_nul.resize(5000, b'x');
+        late_nul.extend_from_slice(b"\0\nhit again\n");
+        let (mmap, buffered) = search_both_ways(&late_nul, config.clone());
+        assert_eq!(positions(&mmap), positions(&buffered));
+        assert_eq!(mmap.len(), 2);
+        let from_reader = searcher("hit", config.clone()).search_reader(late_nul.as_slice()).unwrap();
+        assert_eq!(positions(&from_reader), positions(&mmap));
+
+        let mut early_nul = late_nul.clone();
+        early_nul[100] = 0;
+        let (mmap, buffered) = search_both_ways(&early_nul, config.clone());
+        assert!(mmap.is_empty() && buffered.is_empty());
+        assert!(searcher("hit", config).search_reader(early_nul.as_slice()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn binary_detection_reads_the_whole_window_from_short_reads() {
+        let config = SearcherConfig {
+            binary_detection: true,
+            ..SearcherConfig::default()
+        };
+        // 管道一次只提供几个字节，NUL 在第三次读取时才出现
+        let reader = ChunkedReader::new(&[Some(b"hit\n"), None, Some(b"more "), Some(b"\0 hit\n")]);
+        assert!(searcher("hit", config.clone()).search_reader(reader).unwrap().is_empty());
+        // 数据不足一个窗口时读到结束为止
+        let reader = ChunkedReader::new(&[Some(b"hit\n"), Some(b"miss\n"), Some(b"last hit")]);
+        let lines = searcher("hit", config).search_reader(reader).unwrap();
+        assert_eq!(numbered(&lines), [(1, "hit"), (3, "last hit")]);
+    }
+
+    #[test]
+    fn only_the_leading_bom_is_removed() {
+        let data = b"\xEF\xBB\xBFhit one\n\xEF\xBB\xBFhit two\n";
+        let (mmap, buffered) = search_both_ways(data, SearcherConfig::default());
+        assert_eq!(numbered(&mmap), [(1, "hit one"), (2, "\u{feff}hit two")]);
+        assert_eq!(positions(&mmap), positions(&buffered));
+        assert_eq!((mmap[0].offset, mmap[1].offset), (3, 11));
+
+        // BOM 被拆到几次读取中
+        for binary_detection in [true, false] {
+            let config = SearcherConfig { binary_detection, ..SearcherConfig::default() };
+            let reader = ChunkedReader::new(&[Some(b"\xEF"), None, Some(b"\xBB"), Some(b"\xBFhit one\n\xEF\xBB\xBFhit two\n")]);
+            let mut stats = SearchStats::default();
+            let lines = searcher("hit", config).search_reader_with_stats(reader, &mut stats).unwrap();
+            assert_eq!(positions(&lines), positions(&mmap));
+            assert_eq!(stats.bytes_searched, data.len() as u64);
+        }
+        // 数据只有 BOM
+        let mut stats = SearchStats::default();
+        let reader = ChunkedReader::new(&[Some(b"\xEF\xBB"), Some(b"\xBF")]);
+        let lines = searcher("", SearcherConfig::default()).search_reader_with_stats(reader, &mut stats).unwrap();
+        assert!(lines.is_empty());
+        assert_eq!(stats.bytes_searched, 3);
+    }
+
+    #[test]
+    fn interrupted_read_before_final_unterminated_line() {
+        let reader = ChunkedReader::new(&[Some(b"hit 1\nmiss\n"), None, Some(b"hit 2")]);
+        let lines = searcher("hit", SearcherConfig::default()).search_reader(reader).unwrap();
+        assert_eq!(numbered(&lines), [(1, "hit 1"), (3, "hit 2")]);
+        assert_eq!(lines[1].offset, 11);
+    }
+
+    #[test]
+    fn interrupted_first_read_is_retried() {
+        let reader = ChunkedReader::new(&[None, None, Some(b"hit\n"), None, Some(b"last hit")]);
+        let mut stats = SearchStats::default();
+        let lines = searcher("hit", SearcherConfig::default())
+            .search_reader_with_stats(reader, &mut stats)
+            .unwrap();
+        assert_eq!(numbered(&lines), [(1, "hit"), (2, "last hit")]);
+        assert_eq!(stats.bytes_searched, 12);
+    }
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn utf16_without_bom_must_decode_to_text() {
+        let detect = |data: &[u8]| Encoding::Auto.detect(data);
+        assert_eq!(detect(&utf16le("hit\nmore text\n")), Encoding::Utf16Le);
+        // 交错的 NUL 但解码后是控制字符，或者长度为奇数
+        assert_eq!(detect(&utf16le("hit\u{1}\u{2}\u{3}\u{4}\n")), Encoding::Utf8);
+        assert_eq!(detect(&[1, 0, 2, 0, 3, 0, 4, 0]), Encoding::Utf8);
+        assert_eq!(detect(b"hit\0\n"), Encoding::Utf8);
+        let mut bom = b"\xFF\xFE".to_vec();
+        bom.extend(utf16le("hit\u{1}\u{2}\u{3}\n"));
+        assert_eq!(detect(&bom), Encoding::Utf16Le);
+    }
+
+    #[test]
+    fn guessed_utf16_is_checked_for_binary_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = SearcherConfig { binary_detection: true, ..SearcherConfig::default() };
+        let search = |name: &str, data: &[u8]| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, data).unwrap();
+            numbered(&searcher("hit", config.clone()).search_file_lines(&path).unwrap())
+                .into_iter()
+                .map(|(number, text)| (number, text.to_string()))
+                .collect::<Vec<_>>()
+        };
+        // 少量 U+0000 不影响编码判断，但解码后仍含 NUL
+        let mut text = "hit\n".repeat(30);
+        text.push('\0');
+        assert!(search("guessed", &utf16le(&text)).is_empty());
+        // 有 BOM 时编码是确定的
+        let mut bom = b"\xFF\xFE".to_vec();
+        bom.extend(utf16le(&text));
+        assert_eq!(search("bom", &bom).len(), 30);
+    }
+}
@@ -0,0 +1,98 @@
+//! 按行结束符切分数据，所有搜索路径（mmap、按块读取、调用方提供的内存）共用
+
+/// 逐行遍历一段字节数据，产生 `(行号, 行首字节偏移, 行)`
+///
+/// - 行包含结尾的行结束符（`terminator`），调用方据此区分数据末尾没有行结束符的最后一行；
+/// - `\r\n` 中的 `\r` 属于行内容，由调用方按需去掉；
+/// - 空数据不产生任何行，以行结束符结尾的数据不会多出一个空行。
+///
+/// 按块读取时可以用 `with_position` 从上一块结束的行号和偏移继续编号。
+#[derive(Debug, Clone)]
+pub struct LineIter<'a> {
+    data: &'a [u8],
+    terminator: u8,
+    // 下一行在 data 中的起始位置
+    pos: usize,
+    line_number: usize,
+    // data 第一个字节在整个数据源中的偏移
+    base_offset: usize,
+}
+
+impl<'a> LineIter<'a> {
+    /// 从第 1 行、偏移 0 开始遍历
+    pub fn new(data: &'a [u8], terminator: u8) -> Self {
+        Self::with_position(data, terminator, 1, 0)
+    }
+
+    /// 第一行的行号为 `line_number`，`data` 的第一个字节位于数据源的 `base_offset` 处
+    pub fn with_position(data: &'a [u8], terminator: u8, line_number: usize, base_offset: usize) -> Self {
+        LineIter {
+            data,
+            terminator,
+            pos: 0,
+            line_number,
+            base_offset,
+        }
+    }
+
+    /// 下一行的行号（遍历结束后即数据之后的第一行）
+    pub fn line_number(&self) -> usize {
+        self.line_number
+    }
+}
+
+impl<'a> Iterator for LineIter<'a> {
+    type Item = (usize, usize, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        let rest = &self.data[self.pos..];
+        let len = match memchr::memchr(self.terminator, rest) {
+            Some(end) => end + 1,
+            None => rest.len(),
+        };
+        let item = (self.line_number, self.base_offset + self.pos, &rest[..len]);
+        self.pos += len;
+        self.line_number += 1;
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(data: &[u8], terminator: u8) -> Vec<(usize, usize, &[u8])> {
+        LineIter::new(data, terminator).collect()
+    }
+
+    #[test]
+    fn empty_input() {
+        let mut iter = LineIter::new(b"", b'\n');
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.line_number(), 1);
+    }
+
+    #[test]
+    fn terminators_stay_with_their_line() {
+        assert_eq!(lines(b"a\nbc\n", b'\n'), [(1, 0, &b"a\n"[..]), (2, 2, b"bc\n")]);
+        // 最后一行没有结束符
+        assert_eq!(lines(b"a\nbc", b'\n'), [(1, 0, &b"a\n"[..]), (2, 2, b"bc")]);
+        // `\r` 属于行内容，由调用方处理
+        assert_eq!(lines(b"a\r\n\r\n", b'\n'), [(1, 0, &b"a\r\n"[..]), (2, 3, b"\r\n")]);
+        // `-z`：以 NUL 分隔，换行是普通字节
+        assert_eq!(lines(b"a\nb\0c\0", b'\0'), [(1, 0, &b"a\nb\0"[..]), (2, 4, b"c\0")]);
+    }
+
+    #[test]
+    fn position_continues_from_base() {
+        let mut iter = LineIter::with_position(b"x\n\ny", b'\n', 10, 100);
+        assert_eq!(iter.next(), Some((10, 100, &b"x\n"[..])));
+        assert_eq!(iter.next(), Some((11, 102, &b"\n"[..])));
+        assert_eq!(iter.next(), Some((12, 103, &b"y"[..])));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.line_number(), 13);
+    }
+}